@@ -0,0 +1,207 @@
+// Command line definition.
+//
+// Each action is a subcommand. The original flags (`-i`, `-o`, `-s`, `-l`,
+// `--range` ...) are still accepted at the top level as deprecated aliases.
+
+use clap::{App, AppSettings, Arg, ArgGroup, SubCommand};
+
+/// Options shared by `in`, `out` and `edit`
+fn punch_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![Arg::from_usage("[time] -t, --time <TIME> 'Time of the entry, eg. 9:30am or -15m'")
+             .allow_hyphen_values(true),
+         Arg::from_usage("[memo] -m, --memo <MEMO> 'Memo for the entry'"),
+         Arg::from_usage("[project] -p, --project <PROJECT> 'Project for the entry'"),
+         Arg::from_usage("[tag] --tag <TAG>... 'Tag the entry, may be repeated'")
+             .number_of_values(1)]
+}
+
+/// Options for handling a double clock in or out
+fn policy_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![Arg::from_usage("[policy] --policy <POLICY> 'What to do if already clocked in or out'")
+             .possible_values(&["allow", "warn", "reject", "insert"]),
+         Arg::from_usage("[missing] --missing <TIME> 'Insert the missing entry at this time'")
+             .allow_hyphen_values(true)]
+}
+
+/// Options for rounding totals
+fn rounding_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![Arg::from_usage("[round] -r, --round-up 'Round totals using the configured rounding'"),
+         Arg::from_usage("[round_ex] -R, --round <ROUNDING> 'Round totals up, down, half'")
+             .allow_hyphen_values(true),
+         Arg::from_usage("[round_scope] --round-scope <SCOPE> 'Round each pair, day, week or the whole period'")
+             .possible_values(&["pair", "day", "week", "period"])]
+}
+
+/// Option for machine readable output
+fn format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::from_usage("[format] --format <FORMAT> 'Output format'")
+        .possible_values(&["text", "json", "jsonl"])
+}
+
+/// Option for the days to include. It takes one value so it can't swallow
+/// the option after it.
+fn range_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::from_usage("[range] --range <RANGE> 'Only include these dates, eg. last-week, 2017-Q1 or START..END'")
+        .number_of_values(1)
+        .allow_hyphen_values(true)
+}
+
+/// Options for summaries, apart from the range
+fn report_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let mut args = rounding_args();
+    args.push(Arg::from_usage("[group] --group <PERIOD> 'Subtotal each week, month, year or pay period'")
+                  .possible_values(&["week", "month", "year", "pay-period"]));
+    args.push(Arg::from_usage("[overtime] --overtime 'Split hours into regular, overtime and double time'")
+                  .conflicts_with("group"));
+    args
+}
+
+fn is_index(v: String) -> Result<(), String> {
+    v.parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| format!("'{}' isn't a valid index", v))
+}
+
+fn is_number(v: String) -> Result<(), String> {
+    v.parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| format!("'{}' isn't a valid invoice number", v))
+}
+
+pub fn build_cli() -> App<'static, 'static> {
+    // Using std env macro rather than depending on clap's. No difference
+    // as far as I can tell.
+    App::new(env!("CARGO_PKG_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about(env!("CARGO_PKG_DESCRIPTION"))
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(Arg::from_usage("[config] -c, --config <FILE> 'Use an alternate config file'"))
+        .subcommand(SubCommand::with_name("in")
+                        .about("Record an In entry")
+                        .args(&punch_args())
+                        .args(&policy_args()))
+        .subcommand(SubCommand::with_name("out")
+                        .about("Record an Out entry")
+                        .args(&punch_args())
+                        .args(&policy_args()))
+        .subcommand(SubCommand::with_name("status")
+                        .about("Show whether you're clocked in and today's hours")
+                        .args(&rounding_args()))
+        .subcommand(SubCommand::with_name("log")
+                        .about("Print the full log")
+                        .arg(Arg::from_usage("[pairs] --pairs 'Print matched In and Out pairs'"))
+                        .args(&rounding_args())
+                        .arg(format_arg()))
+        .subcommand(SubCommand::with_name("report")
+                        .about("Print a summary, by default of the current week")
+                        .args(&report_args())
+                        .arg(range_arg())
+                        .arg(format_arg())
+                        .arg(Arg::from_usage("[all] -a, --all 'Summarize the whole log'")
+                                 .conflicts_with("range")))
+        .subcommand(SubCommand::with_name("invoice")
+                        .about("Print an invoice, by default for the current week")
+                        .args(&rounding_args())
+                        .arg(range_arg())
+                        .arg(Arg::from_usage("[all] -a, --all 'Invoice the whole log'")
+                                 .conflicts_with("range"))
+                        .arg(Arg::from_usage("[by] --by <ITEMS> 'Line items per day or per project'")
+                                 .possible_values(&["day", "project"]))
+                        .arg(Arg::from_usage("[format] --format <FORMAT> 'Output format'")
+                                 .possible_values(&["text", "markdown", "html"]))
+                        .arg(Arg::from_usage("[template] --template <FILE> 'Fill in this template'"))
+                        .arg(Arg::from_usage("[number] --number <NUMBER> 'Invoice number, by default the next one'")
+                                 .validator(is_number))
+                        .arg(Arg::from_usage("[preview] --preview 'Don't use up an invoice number'")))
+        .subcommand(SubCommand::with_name("edit")
+                        .about("Change or delete an entry, or list entries by index")
+                        .arg(Arg::from_usage("[INDEX] 'Index of the entry, as listed by `edit`'")
+                                 .validator(is_index))
+                        .arg(Arg::from_usage("[at] --at <TIME> 'Select the entry recorded at this time'")
+                                 .conflicts_with("INDEX"))
+                        .arg(Arg::from_usage("[dir] --dir <DIRECTION> 'Change the direction'")
+                                 .possible_values(&["in", "out"]))
+                        .args(&punch_args())
+                        .arg(Arg::from_usage("[delete] -d, --delete 'Delete the entry'")
+                                 .conflicts_with_all(&["dir", "time", "memo", "project", "tag"])))
+        .subcommand(SubCommand::with_name("undo")
+                        .about("Revert the last punch or edit")
+                        .arg(Arg::from_usage("[yes] -y, --yes 'Don't ask for confirmation'")))
+        .subcommand(SubCommand::with_name("fix")
+                        .about("Go through duplicate entries and missing clock ins and outs, repairing them")
+                        .arg(Arg::from_usage("[yes] -y, --yes 'Accept every proposed repair without asking'")))
+        .subcommand(SubCommand::with_name("import")
+                        .about("Add the entries from another worklog file")
+                        .arg(Arg::from_usage("<FILE> 'File to import'")))
+        .subcommand(SubCommand::with_name("export")
+                        .about("Write the log as csv")
+                        .arg(Arg::from_usage("[FILE] 'Output file, stdout if omitted'")))
+        .subcommand(SubCommand::with_name("check")
+                        .about("Look for mistakes in the log, exits with 1 if there are any")
+                        .arg(Arg::from_usage("[max] --max-interval <LIMIT> 'Longest time clocked in, or none'")))
+        .subcommand(SubCommand::with_name("migrate")
+                        .about("Upgrade the log file to the current format, keeping a backup"))
+        .subcommand(SubCommand::with_name("config")
+                        .about("Print the current configuration")
+                        .arg(Arg::from_usage("[path] --path 'Only print the config file path'")))
+        // Deprecated flags, kept as aliases for the subcommands above
+        .arg(Arg::from_usage("[in] -i, --in 'Record an In entry (deprecated, use `in`)'"))
+        .arg(Arg::from_usage("[out] -o, --out 'Record an Out entry (deprecated, use `out`)'"))
+        .args(&punch_args()
+                   .into_iter()
+                   .chain(policy_args())
+                   .map(|a| a.requires("inout"))
+                   .collect::<Vec<_>>())
+        .arg(Arg::from_usage("[summary] -s, --summary 'Print a summary (deprecated, use `report --all`)'")
+            .conflicts_with("inout"))
+        .arg(Arg::from_usage("[log] -l, --log 'Print the full log (deprecated, use `log`)'")
+            .conflicts_with("summary")
+            .conflicts_with("inout"))
+        .args(&report_args()
+                   .into_iter()
+                   .chain(Some(Arg::from_usage("[range] --range <START> <END> 'Only include these dates (deprecated, use `report --range`)'")
+                                   .allow_hyphen_values(true)))
+                   .map(|a| a.conflicts_with("log").conflicts_with("inout"))
+                   .collect::<Vec<_>>())
+        .group(ArgGroup::with_name("inout").args(&["in", "out"]))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{self, ArgMatches};
+
+    fn parse(args: &[&str]) -> Result<ArgMatches<'static>, clap::Error> {
+        build_cli().get_matches_from_safe(args)
+    }
+
+    #[test]
+    fn range_test() {
+        let matches = parse(&["worklog", "report", "--range", "this-week", "--format", "json"])
+            .unwrap();
+        let report = matches.subcommand_matches("report").unwrap();
+        assert_eq!(report.value_of("range"), Some("this-week"));
+        assert_eq!(report.value_of("format"), Some("json"));
+
+        let matches = parse(&["worklog", "report", "--range", "-7d..today", "--overtime"]).unwrap();
+        let report = matches.subcommand_matches("report").unwrap();
+        assert_eq!(report.value_of("range"), Some("-7d..today"));
+        assert!(report.is_present("overtime"));
+
+        // one value only
+        assert!(parse(&["worklog", "report", "--range", "-7d", "today"]).is_err());
+
+        // the deprecated top level option takes two
+        let matches = parse(&["worklog", "--range", "-7d", "today"]).unwrap();
+        assert_eq!(matches.values_of("range").unwrap().collect::<Vec<_>>(),
+                   vec!["-7d", "today"]);
+    }
+
+    #[test]
+    fn overtime_test() {
+        assert!(parse(&["worklog", "report", "--overtime"]).is_ok());
+        assert!(parse(&["worklog", "report", "--overtime", "--group", "week"]).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use chrono::prelude::*;
+use clap::ArgMatches;
+use commands::{format_entry, open_timesheet};
+use config::Config;
+use error::WorklogError;
+use parsers;
+use timeclock::{self, Lock, ReadMode};
+
+/// Report rows that can't be read and entries that are probably mistakes.
+/// Finding any is an error, so scripts can tell.
+pub fn check(matches: &ArgMatches,
+             config: &Config,
+             ctime: DateTime<FixedOffset>)
+             -> Result<(), WorklogError> {
+    let max_interval = match matches.value_of("max") {
+        Some(m) => try!(parsers::parse_limit(m)),
+        None => config.max_interval,
+    };
+
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, Lock::Shared));
+    let sheet = try!(timeclock::read_with(try!(open_timesheet(config)), ReadMode::Lenient));
+
+    for row in &sheet.bad_rows {
+        println!("line {}: Can't read row: {}", row.line, row.reason());
+        println!("    {:>4} {}", row.line, row.content);
+    }
+
+    let problems = timeclock::check(&sheet.entries, max_interval, ctime);
+    for problem in &problems {
+        let rows = problem.rows();
+        println!("line {}: {}", sheet.lines[rows[0]], problem);
+        for i in rows {
+            println!("    {:>4} {}", sheet.lines[i], format_entry(&sheet.entries[i], config));
+        }
+    }
+
+    match sheet.bad_rows.len() + problems.len() {
+        0 => {
+            println!("No problems found");
+            Ok(())
+        }
+        n => Err(WorklogError::Problems(n)),
+    }
+}
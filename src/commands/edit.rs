@@ -0,0 +1,82 @@
+use chrono::prelude::*;
+use clap::ArgMatches;
+use commands::{format_entry, open_timesheet, save_undo};
+use config::Config;
+use error::WorklogError;
+use parsers;
+use timeclock;
+use timeclock::{Direction, Lock, TimeClockError};
+
+/// Change or delete an entry. Lists the entries with their index if no
+/// entry is selected.
+pub fn edit(matches: &ArgMatches,
+            config: &Config,
+            ctime: DateTime<FixedOffset>)
+            -> Result<(), WorklogError> {
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, Lock::Exclusive));
+    let mut entries = try!(timeclock::read_timesheet(try!(open_timesheet(config))));
+
+    let index = if let Some(i) = matches.value_of("INDEX") {
+        // already validated by clap
+        let i: usize = i.parse().unwrap();
+        if i >= entries.len() {
+            return Err(WorklogError::from(TimeClockError::NoSuchEntry));
+        }
+        i
+    } else if let Some(at) = matches.value_of("at") {
+        let time = try!(parsers::parse_datetime(at, ctime));
+        try!(timeclock::find_entry(&entries, time))
+    } else {
+        for (i, rec) in entries.iter().enumerate() {
+            println!("{:>4} {}", i, format_entry(rec, config));
+        }
+        return Ok(());
+    };
+
+    if matches.is_present("delete") {
+        let old = entries.remove(index);
+        try!(save_undo(config));
+        try!(timeclock::replace_timesheet(&config.data_file, &entries));
+        println!("Deleted {}", format_entry(&old, config));
+        return Ok(());
+    }
+
+    let old = entries[index].clone();
+    {
+        let entry = &mut entries[index];
+        match matches.value_of("dir") {
+            Some("in") => entry.dir = Direction::In,
+            Some("out") => entry.dir = Direction::Out,
+            _ => {}
+        }
+        // times are relative to the entry being edited, so "5:30pm" keeps
+        // the entry's date and "+15m" moves it 15 minutes later
+        if let Some(a) = matches.value_of("time") {
+            entry.time = try!(parsers::parse_datetime(a, entry.time));
+        }
+        if let Some(memo) = matches.value_of("memo") {
+            entry.memo = memo.to_owned();
+        }
+        if let Some(project) = matches.value_of("project") {
+            entry.project = project.to_owned();
+        }
+        if let Some(tags) = matches.values_of("tag") {
+            entry.tags = tags.flat_map(|t| t.split_whitespace())
+                .map(String::from)
+                .collect();
+        }
+    }
+
+    if entries[index] == old {
+        println!("Nothing to change");
+        return Ok(());
+    }
+
+    let new = entries[index].clone();
+    entries.sort_by_key(|k| k.time);
+    try!(save_undo(config));
+    try!(timeclock::replace_timesheet(&config.data_file, &entries));
+    println!("Changed {}", format_entry(&old, config));
+    println!("     to {}", format_entry(&new, config));
+    Ok(())
+}
@@ -0,0 +1,152 @@
+// Interactive repairs.
+//
+// `fix` goes through the duplicate entries, then the gaps reports paper over
+// with a made up "Missing clock in." or "Missing clock out.", proposing a
+// repair for each. Nothing is written until every question is answered.
+
+use chrono::prelude::*;
+use clap::ArgMatches;
+use commands::{ask, format_entry, open_timesheet, save_undo};
+use config::Config;
+use error::WorklogError;
+use parsers;
+use timeclock::{self, Gap, Lock};
+
+/// An answer to a proposed repair
+enum Answer {
+    Accept,
+    Skip,
+    Quit,
+    At(DateTime<FixedOffset>),
+}
+
+/// Ask until the answer makes sense. An empty answer accepts the proposal
+/// if there is one, and a time is parsed relative to `anchor`.
+fn answer(prompt: &str,
+          proposal: bool,
+          anchor: DateTime<FixedOffset>)
+          -> Result<Answer, WorklogError> {
+    loop {
+        let input = match try!(ask(prompt)) {
+            Some(input) => input,
+            None => return Ok(Answer::Quit),
+        };
+        match &input.to_lowercase()[..] {
+            "" | "y" | "yes" if proposal => return Ok(Answer::Accept),
+            "" => continue,
+            "n" | "no" | "s" | "skip" => return Ok(Answer::Skip),
+            "q" | "quit" => return Ok(Answer::Quit),
+            _ => {}
+        }
+        match parsers::parse_datetime(&input, anchor) {
+            Ok(time) => return Ok(Answer::At(time)),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+/// Describe `gap` and ask when the missing entry goes. A time in the
+/// answer always fits the gap.
+fn ask_gap(gap: &Gap, yes: bool, config: &Config) -> Result<Answer, WorklogError> {
+    let missing = gap.missing();
+    println!("Missing clock {:#} for", missing);
+    println!("    {}", format_entry(&gap.entry, config));
+    let prompt = match gap.proposed {
+        Some(t) => {
+            format!("Clock {:#} at {}? [Y]es, [n]o, [q]uit or a time:",
+                    missing,
+                    t.format(&config.datetime_format()))
+        }
+        None => format!("Clock {:#} at what time? [n]o, [q]uit or a time:", missing),
+    };
+    if yes {
+        return Ok(gap.proposed.map_or(Answer::Skip, Answer::At));
+    }
+    loop {
+        let time = match try!(answer(&prompt, gap.proposed.is_some(), gap.entry.time)) {
+            Answer::Accept => gap.proposed.unwrap(),
+            Answer::At(time) => time,
+            a => return Ok(a),
+        };
+        if gap.fits(time) {
+            return Ok(Answer::At(time));
+        }
+        let format = config.datetime_format();
+        match (gap.earliest, gap.latest) {
+            (Some(a), Some(b)) => {
+                println!("The time must be between {} and {}", a.format(&format), b.format(&format))
+            }
+            (Some(a), None) => println!("The time can't be before {}", a.format(&format)),
+            (None, Some(b)) => println!("The time can't be after {}", b.format(&format)),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+fn quit() -> Result<(), WorklogError> {
+    println!("Nothing saved");
+    Ok(())
+}
+
+/// Repair duplicate entries and missing clock ins and outs. With `--yes`
+/// every proposal is accepted, and gaps without one are left alone.
+pub fn fix(matches: &ArgMatches,
+           config: &Config,
+           _ctime: DateTime<FixedOffset>)
+           -> Result<(), WorklogError> {
+    let yes = matches.is_present("yes");
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, Lock::Exclusive));
+    let mut entries = try!(timeclock::read_timesheet(try!(open_timesheet(config))));
+    let mut changes = 0;
+
+    // duplicates first, they'd show up as gaps otherwise
+    let mut remove = vec![false; entries.len()];
+    for i in timeclock::duplicates(&entries) {
+        println!("Duplicate entry");
+        println!("    {}", format_entry(&entries[i], config));
+        let mut accept = yes;
+        while !yes {
+            match try!(answer("Remove it? [Y]es, [n]o or [q]uit:", true, entries[i].time)) {
+                Answer::Accept => accept = true,
+                Answer::Skip => {}
+                Answer::Quit => return quit(),
+                // a time doesn't mean anything here
+                Answer::At(_) => continue,
+            }
+            break;
+        }
+        if accept {
+            remove[i] = true;
+            changes += 1;
+        }
+    }
+    let mut i = 0;
+    entries.retain(|_| {
+                       i += 1;
+                       !remove[i - 1]
+                   });
+
+    let mut fills = Vec::new();
+    for gap in timeclock::gaps(&entries, config.day_start, config.day_end) {
+        match try!(ask_gap(&gap, yes, config)) {
+            Answer::At(time) => {
+                let entry = try!(gap.fill(time));
+                println!("Inserted {}", format_entry(&entry, config));
+                fills.push((gap, entry));
+            }
+            Answer::Quit => return quit(),
+            _ => println!("Skipped"),
+        }
+    }
+    changes += fills.len();
+
+    if changes == 0 {
+        println!("Nothing to fix");
+        return Ok(());
+    }
+    timeclock::fill_gaps(&mut entries, fills);
+    try!(save_undo(config));
+    try!(timeclock::replace_timesheet(&config.data_file, &entries));
+    println!("Saved {} change{}", changes, if changes == 1 { "" } else { "s" });
+    Ok(())
+}
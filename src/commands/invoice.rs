@@ -0,0 +1,183 @@
+// Invoices for a range of days.
+//
+// Invoice numbers are counted in `<data_file>.invoice`, which holds the last
+// number used.
+
+use chrono::prelude::*;
+use clap::ArgMatches;
+use commands::{Summary, open_timesheet, read_timesheet, report_range, rounding, rounding_scope};
+use config::Config;
+use error::WorklogError;
+use invoice::{self, Invoice, InvoiceError, LineItem, Style};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use timeclock::{self, Lock};
+use util::RoundingScope;
+
+fn counter_path(config: &Config) -> PathBuf {
+    let mut name = OsString::from(config.data_file.as_os_str());
+    name.push(".invoice");
+    PathBuf::from(name)
+}
+
+/// The last invoice number used, 0 if there hasn't been one
+fn last_number(config: &Config) -> Result<u64, WorklogError> {
+    let path = counter_path(config);
+    if !path.is_file() {
+        return Ok(0);
+    }
+    let mut s = String::new();
+    try!(try!(File::open(&path)).read_to_string(&mut s));
+    match s.trim().parse() {
+        Ok(n) => Ok(n),
+        Err(_) => Err(WorklogError::from(InvoiceError::Counter(path, s.trim().to_owned()))),
+    }
+}
+
+/// Record `number` as the last invoice number used
+fn save_number(config: &Config, number: u64) -> Result<(), WorklogError> {
+    try!(timeclock::replace_file(&counter_path(config), |file| {
+        try!(writeln!(file, "{}", number));
+        Ok(())
+    }));
+    Ok(())
+}
+
+/// The line items for `summary`, with the time rounded the same as its
+/// earnings. Items by day need each day rounded on its own.
+fn line_items(summary: &Summary,
+              by_project: bool,
+              config: &Config)
+              -> Result<Vec<LineItem>, InvoiceError> {
+    let (groups, rounding) = summary.charged_groups();
+    if by_project {
+        return invoice::items_by_project(groups, rounding, &config.rates);
+    }
+    match summary.scope() {
+        RoundingScope::Pair | RoundingScope::Day => {
+            invoice::items_by_day(groups, rounding, &config.rates)
+        }
+        scope => Err(InvoiceError::Scope(scope)),
+    }
+}
+
+/// Print an invoice for the selected days
+pub fn invoice(matches: &ArgMatches,
+               config: &Config,
+               ctime: DateTime<FixedOffset>)
+               -> Result<(), WorklogError> {
+    let rounding = try!(rounding(matches, config));
+    let scope = rounding_scope(matches, config);
+    let range = try!(report_range(matches, config, ctime));
+    // the counter is read, then written, so only one invoice at a time
+    let lock = if matches.is_present("preview") {
+        Lock::Shared
+    } else {
+        Lock::Exclusive
+    };
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, lock));
+    let entries = try!(read_timesheet(try!(open_timesheet(config)), config));
+    let summary = Summary::new(entries, range, rounding, scope, config);
+
+    let style = match matches.value_of("format") {
+        Some("markdown") => Style::Markdown,
+        Some("html") => Style::Html,
+        _ => Style::Text,
+    };
+    let template_path = matches.value_of("template")
+        .map(PathBuf::from)
+        .or_else(|| config.invoice_template.clone());
+    let template = match template_path {
+        Some(path) => {
+            let mut s = String::new();
+            try!(try!(File::open(path)).read_to_string(&mut s));
+            s
+        }
+        None => invoice::default_template(style).to_owned(),
+    };
+
+    let last = try!(last_number(config));
+    let number = match matches.value_of("number") {
+        Some(n) => n.parse().unwrap(),
+        None => last + 1,
+    };
+    let items = try!(line_items(&summary, matches.value_of("by") == Some("project"), config));
+    let inv = Invoice {
+        number: format!("{}{:04}", config.invoice_prefix, number),
+        date: ctime.date(),
+        // the whole log covers the days in it
+        range: range.or_else(|| match (summary.days.first(), summary.days.last()) {
+                                 (Some(first), Some(last)) => Some((first.date(), last.date())),
+                                 _ => None,
+                             }),
+        items: items,
+        taxes: config.taxes.clone(),
+    };
+    print!("{}",
+           invoice::render(&inv,
+                           &template,
+                           style,
+                           &config.date_format,
+                           &config.currency));
+
+    // Numbering carries on from the highest number used
+    if !matches.is_present("preview") && number > last {
+        try!(save_number(config, number));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+    use std::process;
+    use util::Rounding;
+
+    #[test]
+    fn counter_test() {
+        let mut config = Config::new().unwrap();
+        config.data_file = env::temp_dir().join(format!("worklog-counter-{}.csv", process::id()));
+        assert_eq!(last_number(&config).unwrap(), 0);
+        save_number(&config, 7).unwrap();
+        assert_eq!(last_number(&config).unwrap(), 7);
+
+        fs::write(counter_path(&config), "seven\n").unwrap();
+        let res = last_number(&config);
+        fs::remove_file(counter_path(&config)).unwrap();
+        match res {
+            Err(WorklogError::Invoice(InvoiceError::Counter(_, ref s))) if s == "seven" => {}
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn week_scope_test() {
+        let log = "In,2017-01-02T09:00:00-06:00,,acme\n\
+                   Out,2017-01-02T09:35:00-06:00,\n\
+                   In,2017-01-03T09:00:00-06:00,,acme\n\
+                   Out,2017-01-03T09:35:00-06:00,\n";
+        let mut config = Config::new().unwrap();
+        config.read_str("rate.acme = 100").unwrap();
+        let entries = timeclock::read_timesheet(Cursor::new(log.as_bytes())).unwrap();
+        let rounding = Rounding::Up(Duration::minutes(15));
+        let summary = Summary::new(entries, None, rounding, RoundingScope::Week, &config);
+
+        // rounded once for the week, like the earnings
+        let items = line_items(&summary, true, &config).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].hours(), 1.25);
+        assert_eq!(items[0].amount(), summary.earnings(&config.rates));
+
+        match line_items(&summary, false, &config) {
+            Err(InvoiceError::Scope(RoundingScope::Week)) => {}
+            e => panic!("{:?}", e),
+        }
+    }
+}
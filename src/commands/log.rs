@@ -0,0 +1,64 @@
+use chrono::prelude::*;
+use clap::ArgMatches;
+use commands::{format_entry, open_timesheet, read_timesheet, round_punches, rounding};
+use commands::output::{self, EntryJson, Format, PairJson};
+use config::Config;
+use error::WorklogError;
+use timeclock;
+use timeclock::{Lock, TimeEntry, TimeEntryPair};
+use util;
+
+/// Print every entry in the log, or every pair with `--pairs`
+pub fn log(matches: &ArgMatches,
+           config: &Config,
+           _ctime: DateTime<FixedOffset>)
+           -> Result<(), WorklogError> {
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, Lock::Shared));
+    let csv_entries = try!(read_timesheet(try!(open_timesheet(config)), config));
+    let format = output::format(matches);
+
+    if matches.is_present("pairs") {
+        let rounding = try!(rounding(matches, config));
+        let entries = round_punches(csv_entries, config);
+        let pairs: Vec<TimeEntryPair> =
+            timeclock::timeentry_pairs(entries.into_iter()).collect();
+        if format == Format::Text {
+            let fmt = config.datetime_format();
+            // rounded punches are followed by the time actually punched
+            let time = |entry: &TimeEntry| match entry.raw_time {
+                Some(raw) => {
+                    format!("{} ({})",
+                            entry.time.format(&fmt),
+                            raw.format(&config.time_format))
+                }
+                None => entry.time.format(&fmt).to_string(),
+            };
+            for pair in &pairs {
+                let duration = pair.end()
+                    .time
+                    .signed_duration_since(pair.start().time);
+                let line = format!("{} - {} {:.2} {} {}",
+                                   time(pair.start()),
+                                   time(pair.end()),
+                                   util::hours(util::round(duration, rounding)),
+                                   pair.start().memo,
+                                   pair.start().labels());
+                println!("{}", line.trim_right());
+            }
+            return Ok(());
+        }
+        let json: Vec<PairJson> =
+            pairs.iter().map(|p| PairJson::new(p, rounding)).collect();
+        return output::print_all(&json, format);
+    }
+
+    if format == Format::Text {
+        for rec in csv_entries {
+            println!("{}", format_entry(&rec, config));
+        }
+        Ok(())
+    } else {
+        let json: Vec<EntryJson> = csv_entries.iter().map(EntryJson::new).collect();
+        output::print_all(&json, format)
+    }
+}
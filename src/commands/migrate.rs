@@ -0,0 +1,47 @@
+// Schema migration.
+//
+// Files written before the header line existed are schema version 1.
+// `migrate` copies the file to `<data_file>.v<version>.bak` and rewrites it
+// in the current layout.
+
+use chrono::prelude::*;
+use clap::ArgMatches;
+use commands::open_timesheet;
+use config::Config;
+use error::WorklogError;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use timeclock::{self, Lock, ReadMode, SCHEMA_VERSION};
+
+fn backup_path(config: &Config, version: u32) -> PathBuf {
+    let mut name = OsString::from(config.data_file.as_os_str());
+    name.push(format!(".v{}.bak", version));
+    PathBuf::from(name)
+}
+
+/// Upgrade the timesheet to the current schema version
+pub fn migrate(_matches: &ArgMatches,
+               config: &Config,
+               _ctime: DateTime<FixedOffset>)
+               -> Result<(), WorklogError> {
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, Lock::Exclusive));
+    let sheet = try!(timeclock::read_with(try!(open_timesheet(config)), ReadMode::Strict));
+    let version = sheet.version;
+    if version == SCHEMA_VERSION {
+        println!("{} is already at schema version {}",
+                 config.data_file.display(),
+                 version);
+        return Ok(());
+    }
+
+    let backup = backup_path(config, version);
+    try!(fs::copy(&config.data_file, &backup));
+    try!(timeclock::rewrite_timesheet(&config.data_file, &sheet.entries, SCHEMA_VERSION));
+    println!("Migrated {} from schema version {} to {}, the original is in {}",
+             config.data_file.display(),
+             version,
+             SCHEMA_VERSION,
+             backup.display());
+    Ok(())
+}
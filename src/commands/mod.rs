@@ -0,0 +1,404 @@
+// Implementations of the subcommands. Each takes the subcommand's matches,
+// the configuration and the time worklog was started at.
+
+mod check;
+mod edit;
+mod fix;
+mod invoice;
+mod log;
+mod migrate;
+mod output;
+mod punch;
+mod report;
+mod settings;
+mod status;
+mod transfer;
+mod undo;
+
+pub use self::check::check;
+pub use self::edit::edit;
+pub use self::fix::fix;
+pub use self::invoice::invoice;
+pub use self::log::log;
+pub use self::migrate::migrate;
+pub use self::punch::punch;
+pub use self::report::report;
+pub use self::settings::config;
+pub use self::status::status;
+pub use self::transfer::{export, import};
+pub use self::undo::{save_undo, undo};
+
+use chrono::*;
+use clap::ArgMatches;
+use config::Config;
+use error::WorklogError;
+use parsers;
+use rates::Rates;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use timeclock::{self, DateRecord, ReadMode, RecordGroup, TimeEntry};
+use util;
+
+/// Open the data file for reading and writing, creating it if needed
+pub fn open_timesheet(config: &Config) -> Result<File, WorklogError> {
+    let file = try!(OpenOptions::new()
+                        .read(true)
+                        .write(true)
+                        .create(true)
+                        .open(&config.data_file));
+    Ok(file)
+}
+
+/// Read the timesheet sorted by time. Rows that can't be read are skipped
+/// with a warning, or are an error if `strict` is set.
+///
+/// Commands that rewrite the whole timesheet read it with
+/// `ReadMode::Strict` instead, so a bad row is never silently dropped.
+pub fn read_timesheet<R: Read>(file: R, config: &Config) -> Result<Vec<TimeEntry>, WorklogError> {
+    let mode = if config.strict {
+        ReadMode::Strict
+    } else {
+        ReadMode::Lenient
+    };
+    let mut sheet = try!(timeclock::read_with(file, mode));
+    for row in &sheet.bad_rows {
+        let _ = writeln!(&mut io::stderr(), "Warning: skipped {}", row);
+    }
+    sheet.entries.sort_by_key(|e| e.time);
+    Ok(sheet.entries)
+}
+
+/// Format a `TimeEntry` for display using the configured formats
+pub fn format_entry(entry: &TimeEntry, config: &Config) -> String {
+    let line = format!("{:3} {} {} {}",
+                       entry.dir,
+                       entry.time.format(&config.datetime_format()),
+                       entry.memo,
+                       entry.labels());
+    line.trim_right().to_owned()
+}
+
+
+/// Ask a question on the terminal, returning the trimmed answer or None at
+/// the end of input
+pub fn ask(prompt: &str) -> Result<Option<String>, WorklogError> {
+    print!("{} ", prompt);
+    try!(io::stdout().flush());
+    let mut answer = String::new();
+    if try!(io::stdin().read_line(&mut answer)) == 0 {
+        return Ok(None);
+    }
+    Ok(Some(answer.trim().to_owned()))
+}
+
+/// Ask a yes or no question on the terminal. Anything but y or yes is no.
+pub fn confirm(prompt: &str) -> Result<bool, WorklogError> {
+    let answer = try!(ask(&format!("{} [y/N]", prompt))).unwrap_or_default().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+
+/// The rounding selected by `--round-up` or `--round`, or none
+pub fn rounding(matches: &ArgMatches,
+                config: &Config)
+                -> Result<util::Rounding, WorklogError> {
+    if matches.occurrences_of("round") > 0 {
+        Ok(config.rounding)
+    } else if let Some(r) = matches.value_of("round_ex") {
+        Ok(try!(parsers::parse_rounding(r)))
+    } else {
+        Ok(util::Rounding::None)
+    }
+}
+
+
+/// The days selected by `--range` or `--all`, by default the current week.
+/// None is the whole log.
+pub fn report_range(matches: &ArgMatches,
+                    config: &Config,
+                    ctime: DateTime<FixedOffset>)
+                    -> Result<Option<(Date<FixedOffset>, Date<FixedOffset>)>, WorklogError> {
+    // "summary" is the deprecated top level -s flag
+    let all = matches.is_present("all") || matches.is_present("summary");
+
+    if matches.is_present("range") {
+        // START..END, or two values with the deprecated top level --range.
+        // Each is either a named range or a single day.
+        let values: Vec<&str> = matches.values_of("range")
+            .unwrap()
+            .flat_map(|v| v.splitn(2, ".."))
+            .collect();
+        let mut start = None;
+        let mut end = None;
+        for a in values {
+            let (s, e) = match parsers::parse_range(a, ctime, config.week_start) {
+                Ok(range) => range,
+                Err(_) => {
+                    let date = try!(parsers::parse_datetime(a, ctime)).date();
+                    (date, date)
+                }
+            };
+            start = Some(start.map_or(s, |d: Date<FixedOffset>| d.min(s)));
+            end = Some(end.map_or(e, |d: Date<FixedOffset>| d.max(e)));
+        }
+        Ok(Some((start.unwrap(), end.unwrap())))
+    } else if all {
+        Ok(None)
+    } else {
+        let today = ctime.date();
+        Ok(Some((week_start(today, config), today)))
+    }
+}
+
+
+/// The rounding scope selected by `--round-scope`, or the configured one
+pub fn rounding_scope(matches: &ArgMatches, config: &Config) -> util::RoundingScope {
+    matches.value_of("round_scope")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(config.rounding_scope)
+}
+
+
+/// The grouping selected by `--group`, if any
+pub fn grouping(matches: &ArgMatches) -> Option<util::Grouping> {
+    matches.value_of("group").and_then(|g| g.parse().ok())
+}
+
+
+/// Split date ordered `records` into weeks, months, years or pay periods
+pub fn group(records: &[DateRecord],
+             grouping: util::Grouping,
+             config: &Config)
+             -> Vec<RecordGroup> {
+    timeclock::group_records(records, |date| {
+        util::period(date, grouping, config.week_start, config.pay_period)
+    })
+}
+
+
+/// Apply the configured punch rounding to `entries`
+pub fn round_punches(entries: Vec<TimeEntry>, config: &Config) -> Vec<TimeEntry> {
+    timeclock::round_punches(entries,
+                             config.punch_rounding_in,
+                             config.punch_rounding_out)
+}
+
+
+/// Daily records, and the same rounded according to the rounding scope
+pub struct Summary {
+    /// Unrounded time per day
+    pub raw: Vec<DateRecord>,
+    /// Time per day for display. Only rounded for the pair and day scopes.
+    pub days: Vec<DateRecord>,
+    /// Rounded time per pair, day, week or period, these add up to the
+    /// rounded total
+    pub groups: Vec<DateRecord>,
+    /// Time per day, rounded per pair with the pair scope and otherwise
+    /// unrounded. Earnings are worked out from these, so that each rate's
+    /// time is rounded separately.
+    pub by_pair: Vec<DateRecord>,
+    /// `groups` before they're rounded
+    unrounded_groups: Vec<DateRecord>,
+    rounding: util::Rounding,
+    scope: util::RoundingScope,
+}
+
+impl Summary {
+    /// Collect the days within `range`, or all of them if it's None. Punch
+    /// rounding is applied first.
+    pub fn new(entries: Vec<TimeEntry>,
+               range: Option<(Date<FixedOffset>, Date<FixedOffset>)>,
+               rounding: util::Rounding,
+               scope: util::RoundingScope,
+               config: &Config)
+               -> Summary {
+        let entries = round_punches(entries, config);
+        let in_range = |rec: &DateRecord| match range {
+            Some((start, end)) => start <= rec.date() && rec.date() <= end,
+            None => true,
+        };
+        let raw: Vec<DateRecord> =
+            timeclock::collect_date_records_by(entries.clone(), config.attribution())
+                .into_iter()
+                .filter(|r| in_range(r))
+                .collect();
+        let pairs_rounded: Vec<DateRecord> = if scope == util::RoundingScope::Pair {
+            timeclock::collect_date_records_rounded(entries, config.attribution(), rounding)
+                .into_iter()
+                .filter(|r| in_range(r))
+                .collect()
+        } else {
+            raw.clone()
+        };
+        let groups = timeclock::round_records(&pairs_rounded,
+                                              rounding,
+                                              scope,
+                                              config.week_start);
+        let unrounded_groups = timeclock::scope_records(&pairs_rounded, scope, config.week_start);
+        let days = match scope {
+            util::RoundingScope::Pair => pairs_rounded.clone(),
+            util::RoundingScope::Day => groups.clone(),
+            _ => raw.clone(),
+        };
+        Summary {
+            raw: raw,
+            days: days,
+            groups: groups,
+            by_pair: pairs_rounded,
+            unrounded_groups: unrounded_groups,
+            rounding: rounding,
+            scope: scope,
+        }
+    }
+
+    /// The days as shown, with the rounding of each week or period added to
+    /// its last day so the days add up to the rounded total. It's spread
+    /// over the day's projects and tags.
+    pub fn rounded_days(&self) -> Vec<DateRecord> {
+        let mut days = self.days.clone();
+        if self.scope == util::RoundingScope::Pair || self.scope == util::RoundingScope::Day {
+            return days;
+        }
+        for (k, (group, unrounded)) in self.groups.iter().zip(&self.unrounded_groups).enumerate() {
+            let next = self.groups.get(k + 1).map(|g| g.date());
+            let last = days.iter_mut()
+                .filter(|d| d.date() >= group.date() && next.map_or(true, |n| d.date() < n))
+                .last();
+            if let Some(day) = last {
+                let rounding = group.duration() - unrounded.duration();
+                if day.duration() == Duration::zero() {
+                    // nothing to spread it over
+                    day.add_duration(rounding);
+                } else {
+                    *day = day.scaled(day.duration() + rounding);
+                }
+            }
+        }
+        days
+    }
+
+    /// The rounding left to apply to `by_pair` days to get the days as
+    /// shown, which is none unless the scope is a day
+    pub fn day_rounding(&self) -> util::Rounding {
+        match self.scope {
+            util::RoundingScope::Day => self.rounding,
+            _ => util::Rounding::None,
+        }
+    }
+
+    /// The earnings for one of the `by_pair` days as shown, rounded for the
+    /// pair or day scopes and otherwise unrounded
+    pub fn day_earnings(&self, day: &DateRecord, rates: &Rates) -> f64 {
+        rates.earnings(day, self.day_rounding())
+    }
+
+    /// `groups` before they're rounded, with the rounding for each rate's
+    /// time in them
+    pub fn charged_groups(&self) -> (&[DateRecord], util::Rounding) {
+        let rounding = match self.scope {
+            util::RoundingScope::Pair => util::Rounding::None,
+            _ => self.rounding,
+        };
+        (&self.unrounded_groups, rounding)
+    }
+
+    /// The rounding scope the summary was made with
+    pub fn scope(&self) -> util::RoundingScope {
+        self.scope
+    }
+
+    /// The earnings for each of `groups`
+    pub fn group_earnings(&self, rates: &Rates) -> Vec<f64> {
+        let (groups, rounding) = self.charged_groups();
+        groups.iter().map(|g| rates.earnings(g, rounding)).collect()
+    }
+
+    /// The unrounded total
+    pub fn raw_total(&self) -> Duration {
+        self.raw.iter().fold(Duration::zero(), |acc, r| acc + r.duration())
+    }
+
+    /// The earnings for the rounded time. Each group is charged at the
+    /// rates in effect on its first day.
+    pub fn earnings(&self, rates: &Rates) -> f64 {
+        self.group_earnings(rates).iter().sum()
+    }
+
+    /// The rounded total, with per project and tag totals
+    pub fn total(&self) -> Option<DateRecord> {
+        timeclock::sum_records(&self.groups)
+    }
+}
+
+
+/// The first day of the week containing `date`
+pub fn week_start(date: Date<FixedOffset>, config: &Config) -> Date<FixedOffset> {
+    util::week_start(date, config.week_start)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cli;
+    use std::io::Cursor;
+
+    #[test]
+    fn report_range_test() {
+        let config = Config::new().unwrap();
+        let ctime = DateTime::parse_from_rfc3339("2017-01-10T12:00:00-06:00").unwrap();
+        let range = |args: Vec<&str>| {
+            let matches = cli::build_cli().get_matches_from_safe(args).unwrap();
+            let matches = matches.subcommand_matches("report").cloned().unwrap_or(matches);
+            let (start, end) = report_range(&matches, &config, ctime).unwrap().unwrap();
+            (start.format("%F").to_string(), end.format("%F").to_string())
+        };
+        let dates = |a: &str, b: &str| (a.to_owned(), b.to_owned());
+
+        assert_eq!(range(vec!["worklog", "report", "--range", "2017-01-03", "--format", "json"]),
+                   dates("2017-01-03", "2017-01-03"));
+        assert_eq!(range(vec!["worklog", "report", "--range", "-7d..today"]),
+                   dates("2017-01-03", "2017-01-10"));
+        assert_eq!(range(vec!["worklog", "report", "--range", "2016-Q4..last-week"]),
+                   dates("2016-10-01", "2017-01-06"));
+        assert_eq!(range(vec!["worklog", "--range", "2017-01-03", "today"]),
+                   dates("2017-01-03", "2017-01-10"));
+    }
+
+    #[test]
+    fn rounded_days_test() {
+        // 2017-01-02 is a Monday
+        let log = "In,2017-01-02T09:00:00-06:00,\n\
+                   Out,2017-01-02T09:05:00-06:00,\n\
+                   In,2017-01-03T09:00:00-06:00,\n\
+                   Out,2017-01-03T09:05:00-06:00,\n\
+                   In,2017-01-09T09:00:00-06:00,\n\
+                   Out,2017-01-09T09:10:00-06:00,\n";
+        let mut config = Config::new().unwrap();
+        config.read_str("week_start = mon").unwrap();
+        let entries = timeclock::read_timesheet(Cursor::new(log.as_bytes())).unwrap();
+        let rounding = util::Rounding::Up(Duration::minutes(15));
+        let minutes = |days: Vec<DateRecord>| {
+            days.iter().map(|d| d.duration().num_minutes()).collect::<Vec<_>>()
+        };
+
+        let rounded_days = |scope| {
+            let summary = Summary::new(entries.clone(), None, rounding, scope, &config);
+            minutes(summary.rounded_days())
+        };
+
+        // the rest of the rounding goes on the last day of the week or period
+        assert_eq!(rounded_days(util::RoundingScope::Week), vec![5, 10, 15]);
+        assert_eq!(rounded_days(util::RoundingScope::Period), vec![5, 5, 20]);
+        assert_eq!(rounded_days(util::RoundingScope::Day), vec![15, 15, 15]);
+
+        // on the projects worked on that day
+        let log = log.replace(",\n", ",,acme\n");
+        let entries = timeclock::read_timesheet(Cursor::new(log.as_bytes())).unwrap();
+        let summary = Summary::new(entries, None, rounding, util::RoundingScope::Week, &config);
+        let days = summary.rounded_days();
+        assert_eq!(days[1].projects().keys().collect::<Vec<_>>(), vec!["acme"]);
+        assert_eq!(days[1].projects()["acme"], Duration::minutes(10));
+    }
+}
@@ -0,0 +1,363 @@
+// Machine readable output.
+//
+// `--format json` prints a single JSON document, `--format jsonl` prints one
+// JSON object per line. Times are RFC 3339, dates are `YYYY-MM-DD` with the
+// UTC offset alongside, durations are given both as raw seconds and as
+// rounded hours.
+
+use chrono::Duration;
+use chrono::prelude::*;
+use clap::ArgMatches;
+use commands::{Summary, group};
+use config::Config;
+use error::WorklogError;
+use rates;
+use serde::Serialize;
+use serde_json;
+use std::collections::BTreeMap;
+use timeclock::{DateRecord, Direction, OvertimeDay, OvertimeWeek, PairState, TimeEntry,
+                TimeEntryPair};
+use util;
+
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    JsonLines,
+}
+
+/// The format selected with `--format`
+pub fn format(matches: &ArgMatches) -> Format {
+    match matches.value_of("format") {
+        Some("json") => Format::Json,
+        Some("jsonl") => Format::JsonLines,
+        _ => Format::Text,
+    }
+}
+
+/// Print a list of values as a JSON array, or one per line
+pub fn print_all<T: Serialize>(values: &[T],
+                               format: Format)
+                               -> Result<(), WorklogError> {
+    if format == Format::JsonLines {
+        for v in values {
+            println!("{}", try!(serde_json::to_string(v)));
+        }
+    } else {
+        println!("{}", try!(serde_json::to_string_pretty(values)));
+    }
+    Ok(())
+}
+
+fn hours(duration: Duration, rounding: util::Rounding) -> f64 {
+    util::hours(util::round(duration, rounding))
+}
+
+/// Hours per project or tag
+fn hours_by<'a>(durations: &'a BTreeMap<String, Duration>) -> BTreeMap<&'a str, f64> {
+    durations.iter().map(|(k, v)| (k.as_ref(), util::hours(*v))).collect()
+}
+
+#[derive(Serialize)]
+pub struct EntryJson<'a> {
+    dir: Direction,
+    time: DateTime<FixedOffset>,
+    memo: &'a str,
+    project: &'a str,
+    tags: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_time: Option<DateTime<FixedOffset>>,
+}
+
+impl<'a> EntryJson<'a> {
+    pub fn new(entry: &'a TimeEntry) -> Self {
+        EntryJson {
+            dir: entry.dir,
+            time: entry.time,
+            memo: &entry.memo,
+            project: &entry.project,
+            tags: &entry.tags,
+            raw_time: entry.raw_time,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PairJson<'a> {
+    start: EntryJson<'a>,
+    end: EntryJson<'a>,
+    state: &'static str,
+    seconds: f64,
+    hours: f64,
+}
+
+impl<'a> PairJson<'a> {
+    pub fn new(pair: &'a TimeEntryPair, rounding: util::Rounding) -> Self {
+        let duration = pair.end()
+            .time
+            .signed_duration_since(pair.start().time);
+        PairJson {
+            start: EntryJson::new(pair.start()),
+            end: EntryJson::new(pair.end()),
+            state: match pair.state() {
+                PairState::Complete => "complete",
+                PairState::Open => "open",
+                PairState::MissingIn => "missing_in",
+                PairState::MissingOut => "missing_out",
+            },
+            seconds: util::seconds(duration),
+            hours: hours(duration, rounding),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DayJson<'a> {
+    date: String,
+    offset: String,
+    seconds: f64,
+    hours: f64,
+    memo: &'a str,
+    projects: BTreeMap<&'a str, f64>,
+    tags: BTreeMap<&'a str, f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    earnings: Option<f64>,
+}
+
+impl<'a> DayJson<'a> {
+    /// `raw` gives the seconds and `rounded` the hours
+    pub fn new(raw: &'a DateRecord, rounded: &'a DateRecord, earnings: Option<f64>) -> Self {
+        DayJson {
+            date: raw.date().format("%F").to_string(),
+            offset: raw.date().offset().to_string(),
+            seconds: raw.seconds(),
+            hours: rounded.hours(),
+            memo: raw.memo(),
+            projects: hours_by(rounded.projects()),
+            tags: hours_by(rounded.tags()),
+            earnings: earnings.map(rates::cents),
+        }
+    }
+}
+
+/// Totals over a set of days. Seconds are unrounded, hours are rounded
+/// according to the rounding scope, the same as the text summary.
+#[derive(Serialize)]
+pub struct TotalJson<'a> {
+    seconds: f64,
+    hours: f64,
+    projects: BTreeMap<&'a str, f64>,
+    tags: BTreeMap<&'a str, f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    earnings: Option<f64>,
+}
+
+impl<'a> TotalJson<'a> {
+    pub fn new(raw: Duration, total: Option<&'a DateRecord>, earnings: Option<f64>) -> Self {
+        TotalJson {
+            seconds: util::seconds(raw),
+            hours: total.map_or(0.0, |t| t.hours()),
+            projects: total.map_or_else(BTreeMap::new, |t| hours_by(t.projects())),
+            tags: total.map_or_else(BTreeMap::new, |t| hours_by(t.tags())),
+            earnings: earnings.map(rates::cents),
+        }
+    }
+}
+
+/// Subtotal of a week, month, year or pay period
+#[derive(Serialize)]
+pub struct GroupJson<'a> {
+    start: String,
+    end: String,
+    total: TotalJson<'a>,
+}
+
+/// Hours of a day or week by rate
+#[derive(Serialize)]
+pub struct OvertimeJson {
+    date: String,
+    hours: f64,
+    regular: f64,
+    overtime: f64,
+    double: f64,
+}
+
+impl OvertimeJson {
+    pub fn new(day: &OvertimeDay) -> Self {
+        OvertimeJson {
+            date: day.date.format("%F").to_string(),
+            hours: util::hours(day.total()),
+            regular: util::hours(day.regular),
+            overtime: util::hours(day.overtime),
+            double: util::hours(day.double),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct OvertimeWeekJson {
+    start: String,
+    days: Vec<OvertimeJson>,
+    total: OvertimeJson,
+}
+
+impl OvertimeWeekJson {
+    pub fn new(week: &OvertimeWeek) -> Self {
+        OvertimeWeekJson {
+            start: week.start.format("%F").to_string(),
+            days: week.days.iter().map(OvertimeJson::new).collect(),
+            total: OvertimeJson::new(&week.total()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ReportJson<'a> {
+    start: Option<String>,
+    end: Option<String>,
+    days: Vec<DayJson<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<GroupJson<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overtime: Option<Vec<OvertimeWeekJson>>,
+    total: TotalJson<'a>,
+}
+
+#[derive(Serialize)]
+struct GroupLineJson<'a> {
+    group: GroupJson<'a>,
+}
+
+#[derive(Serialize)]
+struct OvertimeLineJson {
+    overtime: OvertimeWeekJson,
+}
+
+#[derive(Serialize)]
+struct TotalLineJson<'a> {
+    total: TotalJson<'a>,
+}
+
+/// Print a summary. `range` is None for the whole log.
+pub fn print_report(summary: &Summary,
+                    range: Option<(Date<FixedOffset>, Date<FixedOffset>)>,
+                    grouping: Option<util::Grouping>,
+                    overtime: Option<&[OvertimeWeek]>,
+                    config: &Config,
+                    format: Format)
+                    -> Result<(), WorklogError> {
+    let rates = if config.rates.is_empty() {
+        None
+    } else {
+        Some(&config.rates)
+    };
+    let days: Vec<DayJson> = summary.raw
+        .iter()
+        .zip(summary.days.iter())
+        .zip(summary.by_pair.iter())
+        .map(|((raw, rounded), pairs)| {
+                 DayJson::new(raw, rounded, rates.map(|r| summary.day_earnings(pairs, r)))
+             })
+        .collect();
+    let total_rec = summary.total();
+    let total = TotalJson::new(summary.raw_total(),
+                               total_rec.as_ref(),
+                               rates.map(|r| summary.earnings(r)));
+
+    // raw and rounded days have the same dates, so they group the same
+    let subtotals: Vec<_> = match grouping {
+        Some(grouping) => {
+            group(&summary.raw, grouping, config)
+                .into_iter()
+                .zip(group(&summary.days, grouping, config))
+                .zip(group(&summary.by_pair, grouping, config))
+                .map(|((raw, rounded), pairs)| {
+                         let seconds = raw.records
+                             .iter()
+                             .fold(Duration::zero(), |acc, r| acc + r.duration());
+                         let earnings = rates.map(|r| {
+                             pairs.records.iter().map(|d| summary.day_earnings(d, r)).sum::<f64>()
+                         });
+                         (raw.start, raw.end, seconds, rounded.total(), earnings)
+                     })
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    let groups: Vec<GroupJson> = subtotals.iter()
+        .map(|&(start, end, raw, ref total, earnings)| {
+                 GroupJson {
+                     start: start.format("%F").to_string(),
+                     end: end.format("%F").to_string(),
+                     total: TotalJson::new(raw, total.as_ref(), earnings),
+                 }
+             })
+        .collect();
+    let overtime: Option<Vec<OvertimeWeekJson>> =
+        overtime.map(|weeks| weeks.iter().map(OvertimeWeekJson::new).collect());
+
+    if format == Format::JsonLines {
+        try!(print_all(&days, format));
+        for g in groups {
+            println!("{}", try!(serde_json::to_string(&GroupLineJson { group: g })));
+        }
+        for w in overtime.into_iter().flat_map(|o| o) {
+            println!("{}",
+                     try!(serde_json::to_string(&OvertimeLineJson { overtime: w })));
+        }
+        println!("{}",
+                 try!(serde_json::to_string(&TotalLineJson { total: total })));
+    } else {
+        let report = ReportJson {
+            start: range.map(|r| r.0.format("%F").to_string()),
+            end: range.map(|r| r.1.format("%F").to_string()),
+            days: days,
+            groups: groups,
+            overtime: overtime,
+            total: total,
+        };
+        println!("{}", try!(serde_json::to_string_pretty(&report)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use timeclock;
+
+    fn record_helper(date: &str, seconds: i64) -> DateRecord {
+        let x = NaiveDate::parse_from_str(date, "%F").unwrap();
+        let y = FixedOffset::west(6 * 3600).from_local_date(&x).unwrap();
+        DateRecord::from_parts(y, Duration::seconds(seconds), "Test")
+    }
+
+    fn quarter() -> util::Rounding {
+        util::Rounding::Up(Duration::seconds(900))
+    }
+
+    #[test]
+    fn day_json_test() {
+        let rec = record_helper("2017-01-07", 4000);
+        let rounded = rec.rounded(quarter());
+        let day = DayJson::new(&rec, &rounded, None);
+        assert_eq!(serde_json::to_string(&day).unwrap(),
+                   "{\"date\":\"2017-01-07\",\"offset\":\"-06:00\",\
+                   \"seconds\":4000.0,\"hours\":1.25,\"memo\":\"Test\",\
+                   \"projects\":{\"\":1.25},\"tags\":{}}");
+    }
+
+    #[test]
+    fn total_json_test() {
+        let records = vec![record_helper("2017-01-07", 4000),
+                           record_helper("2017-01-08", 3600)];
+        let groups: Vec<DateRecord> =
+            records.iter().map(|r| r.rounded(quarter())).collect();
+        let sum = timeclock::sum_records(&groups).unwrap();
+        let total = TotalJson::new(Duration::seconds(7600), Some(&sum), Some(112.499));
+        assert_eq!(total.seconds, 7600.0);
+        assert_eq!(total.hours, 2.25);
+        assert_eq!(total.projects[""], 2.25);
+        assert_eq!(total.earnings, Some(112.5));
+    }
+}
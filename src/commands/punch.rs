@@ -0,0 +1,154 @@
+use chrono::prelude::*;
+use clap::ArgMatches;
+use commands::{format_entry, open_timesheet, read_timesheet, save_undo};
+use config::{Config, PunchPolicy};
+use error::WorklogError;
+use parsers;
+use std::io;
+use std::io::prelude::*;
+use timeclock;
+use timeclock::{Direction, Lock, TimeClockError, TimeEntry};
+
+/// Default time for a missing entry. A missing Out goes at the end of the
+/// day of the In before it, a missing In at the start of the day of the Out
+/// after it. None if that isn't between the two entries, eg. for an evening
+/// shift that starts after `day_end`.
+fn missing_time(prev: &TimeEntry,
+                entry: &TimeEntry,
+                config: &Config)
+                -> Option<DateTime<FixedOffset>> {
+    let time = match prev.dir {
+        Direction::In => prev.time.date().and_time(config.day_end),
+        Direction::Out => entry.time.date().and_time(config.day_start),
+    };
+    time.and_then(|t| if prev.time <= t && t <= entry.time {
+                      Some(t)
+                  } else {
+                      None
+                  })
+}
+
+/// Record an In or Out entry
+pub fn punch(dir: Direction,
+             matches: &ArgMatches,
+             config: &Config,
+             ctime: DateTime<FixedOffset>)
+             -> Result<(), WorklogError> {
+    let time = match matches.value_of("time") {
+        Some(a) => try!(parsers::parse_datetime(a, ctime)),
+        None => ctime,
+    };
+
+    let memo = matches.value_of("memo").unwrap_or(&config.memo);
+    let mut entry = TimeEntry::new(dir, time, memo);
+    entry.project = matches.value_of("project").unwrap_or("").to_owned();
+    if let Some(tags) = matches.values_of("tag") {
+        entry.tags = tags.flat_map(|t| t.split_whitespace())
+            .map(String::from)
+            .collect();
+    }
+
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, Lock::Exclusive));
+    let entries = try!(read_timesheet(try!(open_timesheet(config)), config));
+
+    // giving a time for the missing entry implies inserting it
+    let policy = if matches.is_present("missing") {
+        PunchPolicy::Insert
+    } else {
+        match matches.value_of("policy") {
+            Some(p) => p.parse().unwrap(), // already validated by clap
+            None => config.double_punch,
+        }
+    };
+
+    let mut missing = None;
+    if let Some(prev) = timeclock::double_punch(&entries, &entry) {
+        match policy {
+            PunchPolicy::Allow => {}
+            PunchPolicy::Warn => {
+                let _ = writeln!(&mut io::stderr(),
+                                 "Warning: already clocked {:#} at {}",
+                                 prev.dir,
+                                 prev.time.format(&config.datetime_format()));
+            }
+            PunchPolicy::Reject => {
+                let time = prev.time.format(&config.datetime_format()).to_string();
+                return Err(WorklogError::from(TimeClockError::DoublePunch(prev.dir, time)));
+            }
+            PunchPolicy::Insert => {
+                // the missing entry's time is relative to the day it belongs to
+                let mtime = match matches.value_of("missing") {
+                    Some(a) => {
+                        let anchor = match prev.dir {
+                            Direction::In => prev.time,
+                            Direction::Out => entry.time,
+                        };
+                        try!(parsers::parse_datetime(a, anchor))
+                    }
+                    None => {
+                        match missing_time(prev, &entry, config) {
+                            Some(t) => t,
+                            None => {
+                                let missing = match dir {
+                                    Direction::In => Direction::Out,
+                                    Direction::Out => Direction::In,
+                                };
+                                let format = config.datetime_format();
+                                let after = prev.time.format(&format).to_string();
+                                let before = time.format(&format).to_string();
+                                let err = TimeClockError::NoMissingTime(missing, after, before);
+                                return Err(WorklogError::from(err));
+                            }
+                        }
+                    }
+                };
+                missing = Some(try!(timeclock::missing_entry(prev, &entry, mtime)));
+            }
+        }
+    } else if matches.is_present("missing") {
+        let _ = writeln!(&mut io::stderr(),
+                         "Warning: not already clocked {:#}, ignoring --missing",
+                         dir);
+    }
+
+    try!(save_undo(config));
+    let mut csv_file = try!(open_timesheet(config));
+    match missing {
+        Some(ref m) => {
+            try!(timeclock::mark_times(&[m.clone(), entry.clone()], &mut csv_file));
+            println!("Inserted {}", format_entry(m, config));
+        }
+        None => try!(timeclock::mark_time(&entry, &mut csv_file)),
+    }
+
+    println!("Clocked {:#} at {}",
+             dir,
+             time.format(&config.datetime_format()));
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_time_test() {
+        let mut config = Config::new().unwrap();
+        config.read_str("day_start = 08:00\nday_end = 17:00").unwrap();
+        let entry = |dir, s| TimeEntry::new(dir, DateTime::parse_from_rfc3339(s).unwrap(), "");
+        let time = |s| Some(DateTime::parse_from_rfc3339(s).unwrap());
+
+        let day = entry(Direction::In, "2017-01-02T09:00:00-06:00");
+        let next = entry(Direction::In, "2017-01-03T09:00:00-06:00");
+        assert_eq!(missing_time(&day, &next, &config), time("2017-01-02T17:00:00-06:00"));
+        let out = entry(Direction::Out, "2017-01-02T17:00:00-06:00");
+        let next = entry(Direction::Out, "2017-01-03T17:00:00-06:00");
+        assert_eq!(missing_time(&out, &next, &config), time("2017-01-03T08:00:00-06:00"));
+
+        // an evening shift starts after the usual end of the day
+        let evening = entry(Direction::In, "2017-01-02T18:00:00-06:00");
+        let next = entry(Direction::In, "2017-01-03T18:00:00-06:00");
+        assert_eq!(missing_time(&evening, &next, &config), None);
+    }
+}
@@ -0,0 +1,181 @@
+use chrono::*;
+use clap::ArgMatches;
+use commands::{Summary, group, grouping, open_timesheet, read_timesheet, report_range, rounding, rounding_scope};
+use commands::output::{self, Format};
+use config::Config;
+use error::WorklogError;
+use rates;
+use std::collections::BTreeMap;
+use timeclock::{self, Lock};
+use util;
+
+/// Print per project and per tag hours
+fn print_breakdown(durations: &BTreeMap<String, Duration>, prefix: &str) {
+    for (name, duration) in durations {
+        let name = if name.is_empty() { "(none)" } else { name };
+        println!("    {}{} {:.2}", prefix, name, util::hours(*duration));
+    }
+}
+
+
+/// The earnings for `amount` to follow hours, or nothing without rates
+fn earnings(amount: f64, config: &Config) -> String {
+    if config.rates.is_empty() {
+        String::new()
+    } else {
+        format!(" {}", rates::format_amount(amount, &config.currency))
+    }
+}
+
+
+/// Print `days` as shown, with the earnings for the same days in `by_pair`
+fn print_days(days: &[timeclock::DateRecord],
+              by_pair: &[timeclock::DateRecord],
+              summary: &Summary,
+              config: &Config) {
+    for (rec, pairs) in days.iter().zip(by_pair) {
+        println!("{} {:.2}{} {}",
+                 rec.date().format(&config.date_format),
+                 rec.hours(),
+                 earnings(summary.day_earnings(pairs, &config.rates), config),
+                 rec.memo());
+        if rec.has_labels() {
+            print_breakdown(rec.projects(), "");
+            print_breakdown(rec.tags(), "#");
+        }
+    }
+}
+
+
+/// Print regular, overtime and double time hours by day and week, and the
+/// earnings with all the hours at the usual rates
+fn print_overtime(weeks: &[timeclock::OvertimeWeek], summary: &Summary, config: &Config) {
+    let split = |d: &timeclock::OvertimeDay| {
+        format!("{:.2} ({:.2} regular, {:.2} overtime, {:.2} double)",
+                util::hours(d.total()),
+                util::hours(d.regular),
+                util::hours(d.overtime),
+                util::hours(d.double))
+    };
+    for week in weeks {
+        for day in &week.days {
+            println!("{} {}", day.date.format(&config.date_format), split(day));
+        }
+        println!("Week of {}: {}",
+                 week.start.format(&config.date_format),
+                 split(&week.total()));
+    }
+    if let Some(first) = weeks.first() {
+        let days = weeks.iter().flat_map(|w| w.days.iter());
+        println!("Total Hours: {}",
+                 split(&timeclock::OvertimeDay::sum(first.start, days)));
+    }
+    if !config.rates.is_empty() {
+        println!("Total Earnings at base rates: {}",
+                 rates::format_amount(summary.earnings(&config.rates), &config.currency));
+    }
+}
+
+
+fn print_short_summary(summary: &Summary,
+                       rounding: util::Rounding,
+                       scope: util::RoundingScope,
+                       grouping: Option<util::Grouping>,
+                       config: &Config) {
+    match grouping {
+        Some(grouping) => {
+            // the days and the days by pair have the same dates, so they
+            // group the same
+            let by_pair = group(&summary.by_pair, grouping, config);
+            for (g, pairs) in group(&summary.days, grouping, config).into_iter().zip(by_pair) {
+                print_days(&g.records, &pairs.records, summary, config);
+                if let Some(total) = g.total() {
+                    let amount = pairs.records
+                        .iter()
+                        .map(|d| summary.day_earnings(d, &config.rates))
+                        .sum();
+                    println!("Subtotal {} to {}: {:.2}{}",
+                             g.start.format(&config.date_format),
+                             g.end.format(&config.date_format),
+                             total.hours(),
+                             earnings(amount, config));
+                    if total.has_labels() {
+                        print_breakdown(total.projects(), "");
+                        print_breakdown(total.tags(), "#");
+                    }
+                }
+            }
+        }
+        None => print_days(&summary.days, &summary.by_pair, summary, config),
+    }
+    if scope == util::RoundingScope::Week {
+        let amounts = summary.group_earnings(&config.rates);
+        for (week, amount) in summary.groups.iter().zip(amounts) {
+            println!("Week of {}: {:.2}{}",
+                     week.date().format(&config.date_format),
+                     week.hours(),
+                     earnings(amount, config));
+        }
+    }
+
+    let total = summary.total();
+    let hours = total.as_ref().map_or(0.0, |t| t.hours());
+    if rounding == util::Rounding::None {
+        println!("Total Hours: {:.2}", hours);
+    } else {
+        println!("Total Hours: {:.2} ({:.2} unrounded)",
+                 hours,
+                 util::hours(summary.raw_total()));
+    }
+    if let Some(total) = total {
+        if total.has_labels() {
+            print_breakdown(total.projects(), "");
+            print_breakdown(total.tags(), "#");
+        }
+    }
+    if !config.rates.is_empty() {
+        println!("Total Earnings at base rates: {}",
+                 rates::format_amount(summary.earnings(&config.rates), &config.currency));
+    }
+}
+
+
+/// Print a summary of the current week, a range, or the whole log
+pub fn report(matches: &ArgMatches,
+              config: &Config,
+              ctime: DateTime<FixedOffset>)
+              -> Result<(), WorklogError> {
+    let rounding = try!(rounding(matches, config));
+
+    let range = try!(report_range(matches, config, ctime));
+
+    let scope = rounding_scope(matches, config);
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, Lock::Shared));
+    let csv_entries = try!(read_timesheet(try!(open_timesheet(config)), config));
+    let summary = Summary::new(csv_entries, range, rounding, scope, config);
+    let grouping = grouping(matches);
+    let overtime = if matches.is_present("overtime") {
+        Some(timeclock::overtime(&summary.rounded_days(), &config.overtime, config.week_start))
+    } else {
+        None
+    };
+
+    match (output::format(matches), overtime) {
+        (Format::Text, Some(weeks)) => {
+            print_overtime(&weeks, &summary, config);
+            Ok(())
+        }
+        (Format::Text, None) => {
+            print_short_summary(&summary, rounding, scope, grouping, config);
+            Ok(())
+        }
+        (format, overtime) => {
+            output::print_report(&summary,
+                                 range,
+                                 grouping,
+                                 overtime.as_ref().map(|w| &w[..]),
+                                 config,
+                                 format)
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use chrono::prelude::*;
+use clap::ArgMatches;
+use config;
+use config::Config;
+use error::WorklogError;
+
+/// Print the configuration in config file format
+pub fn config(matches: &ArgMatches,
+              config: &Config,
+              _ctime: DateTime<FixedOffset>)
+              -> Result<(), WorklogError> {
+    if matches.is_present("path") {
+        match config.path.clone().or_else(config::default_path) {
+            Some(path) => println!("{}", path.display()),
+            None => println!("No config file"),
+        }
+    } else {
+        print!("{}", config);
+    }
+    Ok(())
+}
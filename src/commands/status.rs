@@ -0,0 +1,52 @@
+use chrono::prelude::*;
+use clap::ArgMatches;
+use commands::{Summary, open_timesheet, read_timesheet, rounding, rounding_scope, week_start};
+use config::Config;
+use error::WorklogError;
+use timeclock;
+use timeclock::{Lock, PairState};
+
+/// Print whether we're clocked in, and the hours for today and this week
+pub fn status(matches: &ArgMatches,
+              config: &Config,
+              ctime: DateTime<FixedOffset>)
+              -> Result<(), WorklogError> {
+    let rounding = try!(rounding(matches, config));
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, Lock::Shared));
+    let entries = try!(read_timesheet(try!(open_timesheet(config)), config));
+
+    let last_pair = timeclock::timeentry_pairs(entries.iter().cloned()).last();
+    match last_pair {
+        Some(ref pair) if pair.state() == PairState::Open => {
+            let start = pair.start();
+            let elapsed = ctime.signed_duration_since(start.time);
+            let line = format!("Clocked in since {} ({}:{:02} elapsed) {}",
+                               start.time.format(&config.datetime_format()),
+                               elapsed.num_hours(),
+                               elapsed.num_minutes() % 60,
+                               start.labels());
+            println!("{}", line.trim_right());
+        }
+        Some(ref pair) => {
+            println!("Clocked out since {}",
+                     pair.end().time.format(&config.datetime_format()));
+        }
+        None => {
+            println!("No entries");
+            return Ok(());
+        }
+    }
+
+    let today = ctime.date();
+    let start = week_start(today, config);
+    let scope = rounding_scope(matches, config);
+    let summary = Summary::new(entries, Some((start, today)), rounding, scope, config);
+    let today_hours = summary.days
+        .iter()
+        .filter(|rec| rec.date() == today)
+        .fold(0.0, |acc, rec| acc + rec.hours());
+    let week_hours = summary.total().map_or(0.0, |t| t.hours());
+    println!("Today: {:.2}", today_hours);
+    println!("This week: {:.2}", week_hours);
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use chrono::prelude::*;
+use clap::ArgMatches;
+use commands::{open_timesheet, read_timesheet, save_undo};
+use config::Config;
+use error::WorklogError;
+use std::fs::File;
+use std::io;
+use timeclock::{self, Lock, SCHEMA_VERSION};
+
+/// Append the entries of another worklog file, skipping ones we already have
+pub fn import(matches: &ArgMatches,
+              config: &Config,
+              _ctime: DateTime<FixedOffset>)
+              -> Result<(), WorklogError> {
+    let path = matches.value_of("FILE").unwrap();
+    let new_entries = try!(timeclock::read_timesheet(try!(File::open(path))));
+
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, Lock::Exclusive));
+    let mut csv_file = try!(open_timesheet(config));
+    let entries = try!(read_timesheet(&csv_file, config));
+
+    try!(save_undo(config));
+    let mut skipped = 0;
+    let mut imported = 0;
+    for entry in new_entries {
+        if entries.contains(&entry) {
+            skipped += 1;
+        } else {
+            try!(timeclock::mark_time(&entry, &mut csv_file));
+            imported += 1;
+        }
+    }
+
+    println!("Imported {} entries, skipped {} duplicates", imported, skipped);
+    Ok(())
+}
+
+
+/// Write the log as csv to a file or stdout
+pub fn export(matches: &ArgMatches,
+              config: &Config,
+              _ctime: DateTime<FixedOffset>)
+              -> Result<(), WorklogError> {
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, Lock::Shared));
+    let entries = try!(read_timesheet(try!(open_timesheet(config)), config));
+    match matches.value_of("FILE") {
+        Some(path) => {
+            let file = try!(File::create(path));
+            try!(timeclock::write_timesheet(&entries, SCHEMA_VERSION, file))
+        }
+        None => try!(timeclock::write_timesheet(&entries, SCHEMA_VERSION, io::stdout())),
+    }
+    Ok(())
+}
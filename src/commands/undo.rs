@@ -0,0 +1,122 @@
+// Single level undo.
+//
+// Before a command changes the timesheet it copies it to `<data_file>.undo`.
+// `undo` puts that copy back and keeps it, so undoing again finds nothing to
+// undo until the next change. Timesheets written before the undo file existed
+// fall back to removing the last row in the file.
+
+use chrono::prelude::*;
+use clap::ArgMatches;
+use commands::{confirm, open_timesheet};
+use config::Config;
+use error::WorklogError;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use timeclock;
+use timeclock::{Lock, TimeEntry};
+
+fn undo_path(config: &Config) -> PathBuf {
+    let mut name = OsString::from(config.data_file.as_os_str());
+    name.push(".undo");
+    PathBuf::from(name)
+}
+
+/// Save a copy of the timesheet so the next change can be undone
+pub fn save_undo(config: &Config) -> Result<(), WorklogError> {
+    let data = match File::open(&config.data_file) {
+        Ok(file) => Some(file),
+        // nothing recorded yet, undoing restores an empty timesheet
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(WorklogError::from(e)),
+    };
+    try!(timeclock::replace_file(&undo_path(config), move |file| {
+        if let Some(mut data) = data {
+            try!(io::copy(&mut data, file));
+        }
+        Ok(())
+    }));
+    Ok(())
+}
+
+/// Revert the last change to the timesheet
+pub fn undo(matches: &ArgMatches,
+            config: &Config,
+            _ctime: DateTime<FixedOffset>)
+            -> Result<(), WorklogError> {
+    let _lock = try!(timeclock::lock_timesheet(&config.data_file, Lock::Exclusive));
+    let current = try!(timeclock::read_entries(try!(open_timesheet(config))));
+    let path = undo_path(config);
+
+    let previous = if path.is_file() {
+        try!(timeclock::read_entries(try!(File::open(&path))))
+    } else {
+        let mut previous = current.clone();
+        previous.pop();
+        previous
+    };
+
+    let removed: Vec<&TimeEntry> =
+        current.iter().filter(|e| !previous.contains(e)).collect();
+    let restored: Vec<&TimeEntry> =
+        previous.iter().filter(|e| !current.contains(e)).collect();
+
+    if removed.is_empty() && restored.is_empty() {
+        println!("Nothing to undo");
+        return Ok(());
+    }
+
+    for entry in &removed {
+        println!("Remove  {}", entry);
+    }
+    for entry in &restored {
+        println!("Restore {}", entry);
+    }
+
+    if !matches.is_present("yes") && !try!(confirm("Undo?")) {
+        return Ok(());
+    }
+
+    // the copy stays, it matches the timesheet now
+    try!(timeclock::replace_timesheet(&config.data_file, &previous));
+    println!("Undone");
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cli;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn undo_twice_test() {
+        let mut config = Config::new().unwrap();
+        config.data_file = env::temp_dir().join(format!("worklog-undo-{}.csv", process::id()));
+        let ctime = DateTime::parse_from_rfc3339("2017-01-02T12:00:00-06:00").unwrap();
+        let matches = cli::build_cli().get_matches_from(vec!["worklog", "undo", "-y"]);
+        let matches = matches.subcommand_matches("undo").unwrap();
+        let entry = |s| TimeEntry::new(timeclock::Direction::In,
+                                       DateTime::parse_from_rfc3339(s).unwrap(),
+                                       "");
+        let first = entry("2017-01-02T09:00:00-06:00");
+        let second = entry("2017-01-02T10:00:00-06:00");
+
+        timeclock::replace_timesheet(&config.data_file, &[first.clone()]).unwrap();
+        save_undo(&config).unwrap();
+        timeclock::replace_timesheet(&config.data_file, &[first.clone(), second]).unwrap();
+        undo(matches, &config, ctime).unwrap();
+        let after_one = timeclock::read_entries(File::open(&config.data_file).unwrap()).unwrap();
+        // a second undo leaves the entry before the change alone
+        undo(matches, &config, ctime).unwrap();
+        let after_two = timeclock::read_entries(File::open(&config.data_file).unwrap()).unwrap();
+        fs::remove_file(&config.data_file).unwrap();
+        fs::remove_file(undo_path(&config)).unwrap();
+        assert_eq!(after_one, vec![first.clone()]);
+        assert_eq!(after_two, vec![first]);
+    }
+}
@@ -0,0 +1,621 @@
+// Configuration loading.
+//
+// The config file is a simple INI-style list of `key = value` pairs. Blank
+// lines and lines starting with `#` or `;` are ignored, as are `[section]`
+// headers. Values may optionally be wrapped in double quotes.
+//
+//     # ~/.config/worklog/config
+//     week_start = sat
+//     pay_period = weekly
+//     rounding = +15m
+//     data_file = ~/Documents/worklog.csv
+//     date_format = "%F"
+//     time_format = "%I:%M %P"
+//     memo = ""
+//     double_punch = warn
+//     day_start = 09:00
+//     day_end = 17:00
+//     split_days = false
+//     day_boundary = 00:00
+//     strict = false
+//     max_interval = 16h
+//
+// Every key can also be set through an environment variable of the form
+// `WORKLOG_<KEY>`, eg. `WORKLOG_WEEK_START=mon`, which takes precedence over
+// the file.
+
+use chrono::{Duration, NaiveTime, Weekday};
+use parsers;
+use rates::Rates;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use timeclock::{DayAttribution, OvertimeRules};
+use util::{self, PayPeriod, PunchRounding, Rounding, RoundingScope};
+
+#[cfg(target_family = "unix")]
+static CSV_FILE_NAME: &'static str = ".worklog.csv";
+#[cfg(target_family = "windows")]
+static CSV_FILE_NAME: &'static str = "worklog.csv";
+
+static ENV_PREFIX: &'static str = "WORKLOG_";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    NoHome,
+    Syntax(usize, String),
+    UnknownKey(usize, String),
+    InvalidValue(String, String),
+}
+
+impl Error for ConfigError {
+    fn description(&self) -> &str {
+        match *self {
+            ConfigError::Io(_, ref err) => err.description(),
+            ConfigError::NoHome => "Unable to locate the home directory",
+            ConfigError::Syntax(_, _) => "Invalid line in config file",
+            ConfigError::UnknownKey(_, _) => "Unknown key in config file",
+            ConfigError::InvalidValue(_, _) => "Invalid config value",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            ConfigError::Io(_, ref err) => Some(err as &Error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref path, ref err) => {
+                write!(f, "{}: {}", path.display(), err)
+            }
+            ConfigError::Syntax(line, ref s) => {
+                write!(f, "{} on line {}: '{}'", self.description(), line, s)
+            }
+            ConfigError::UnknownKey(line, ref key) => {
+                write!(f, "{} on line {}: '{}'", self.description(), line, key)
+            }
+            ConfigError::InvalidValue(ref key, ref value) => {
+                write!(f, "{} for '{}': '{}'", self.description(), key, value)
+            }
+            ConfigError::NoHome => fmt::Display::fmt(self.description(), f),
+        }
+    }
+}
+
+/// What to do when punching in while already in, or out while already out
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum PunchPolicy {
+    /// Record the entry
+    Allow,
+    /// Record the entry and print a warning
+    Warn,
+    /// Refuse to record the entry
+    Reject,
+    /// Insert the missing opposite entry at the start or end of the day
+    Insert,
+}
+
+impl FromStr for PunchPolicy {
+    type Err = ();
+    fn from_str(s: &str) -> Result<PunchPolicy, ()> {
+        match s.to_lowercase().as_ref() {
+            "allow" => Ok(PunchPolicy::Allow),
+            "warn" => Ok(PunchPolicy::Warn),
+            "reject" => Ok(PunchPolicy::Reject),
+            "insert" => Ok(PunchPolicy::Insert),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for PunchPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            PunchPolicy::Allow => "allow",
+            PunchPolicy::Warn => "warn",
+            PunchPolicy::Reject => "reject",
+            PunchPolicy::Insert => "insert",
+        };
+        fmt::Display::fmt(s, f)
+    }
+}
+
+/// User configurable settings
+#[derive(Clone,Debug,PartialEq)]
+pub struct Config {
+    /// First day of the week for the default summary
+    pub week_start: Weekday,
+    /// Pay periods for `report --group pay-period`
+    pub pay_period: PayPeriod,
+    /// Rounding mode used by `--round-up`
+    pub rounding: Rounding,
+    /// Whether pairs, days, weeks or the whole report are rounded
+    pub rounding_scope: RoundingScope,
+    /// Rounding of In times, before they're paired up
+    pub punch_rounding_in: Option<PunchRounding>,
+    /// Rounding of Out times, before they're paired up
+    pub punch_rounding_out: Option<PunchRounding>,
+    /// When time counts as overtime or double time
+    pub overtime: OvertimeRules,
+    /// Hourly rates for earnings
+    pub rates: Rates,
+    /// Currency symbol for earnings
+    pub currency: String,
+    /// Template file for invoices, instead of the built in one
+    pub invoice_template: Option<PathBuf>,
+    /// Put before invoice numbers, eg. `INV-`
+    pub invoice_prefix: String,
+    /// Taxes added to invoices, by name, in percent
+    pub taxes: Vec<(String, f64)>,
+    /// Location of the csv data file
+    pub data_file: PathBuf,
+    /// strftime format for displaying dates
+    pub date_format: String,
+    /// strftime format for displaying times
+    pub time_format: String,
+    /// Memo used when none is given
+    pub memo: String,
+    /// How to handle a double clock in or clock out
+    pub double_punch: PunchPolicy,
+    /// Usual start of the working day
+    pub day_start: NaiveTime,
+    /// Usual end of the working day
+    pub day_end: NaiveTime,
+    /// Split intervals that cross the day boundary between the two days
+    pub split_days: bool,
+    /// Time of day at which one day ends and the next begins
+    pub day_boundary: NaiveTime,
+    /// Fail on rows of the log that can't be read instead of skipping them
+    pub strict: bool,
+    /// Longest time between clocking in and out that `check` accepts
+    pub max_interval: Option<Duration>,
+    /// The config file that was loaded, if any
+    pub path: Option<PathBuf>,
+}
+
+impl Config {
+    /// The built-in defaults. Fails only if the home directory is unknown.
+    pub fn new() -> Result<Config, ConfigError> {
+        let mut data_file = try!(env::home_dir().ok_or(ConfigError::NoHome));
+        data_file.push(CSV_FILE_NAME);
+        Ok(Config {
+            week_start: Weekday::Sat,
+            pay_period: PayPeriod::Weekly,
+            rounding: Rounding::Up(Duration::seconds(900)),
+            rounding_scope: RoundingScope::Day,
+            punch_rounding_in: None,
+            punch_rounding_out: None,
+            overtime: OvertimeRules::new(),
+            rates: Rates::new(),
+            currency: String::from("$"),
+            invoice_template: None,
+            invoice_prefix: String::new(),
+            taxes: Vec::new(),
+            data_file: data_file,
+            date_format: String::from("%F"),
+            time_format: String::from("%I:%M %P"),
+            memo: String::new(),
+            double_punch: PunchPolicy::Warn,
+            day_start: NaiveTime::from_hms(9, 0, 0),
+            day_end: NaiveTime::from_hms(17, 0, 0),
+            split_days: false,
+            day_boundary: NaiveTime::from_hms(0, 0, 0),
+            strict: false,
+            max_interval: Some(Duration::hours(16)),
+            path: None,
+        })
+    }
+
+    /// Load the configuration.
+    ///
+    /// If `path` is given it must exist, otherwise the default location is
+    /// used if there's a file there. Environment overrides are applied last.
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let mut config = try!(Config::new());
+        let path = match path {
+            Some(p) => Some(p.to_owned()),
+            None => default_path().and_then(|p| if p.is_file() {
+                                                    Some(p)
+                                                } else {
+                                                    None
+                                                }),
+        };
+
+        if let Some(path) = path {
+            let mut s = String::new();
+            try!(File::open(&path)
+                     .and_then(|mut f| f.read_to_string(&mut s))
+                     .map_err(|e| ConfigError::Io(path.clone(), e)));
+            try!(config.read_str(&s));
+            config.path = Some(path);
+        }
+
+        try!(config.apply_env(env::vars()));
+        Ok(config)
+    }
+
+    /// Apply settings from the contents of a config file
+    pub fn read_str(&mut self, s: &str) -> Result<(), ConfigError> {
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') ||
+               line.starts_with(';') ||
+               (line.starts_with('[') && line.ends_with(']')) {
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(n) => (line[..n].trim(), line[n + 1..].trim()),
+                None => return Err(ConfigError::Syntax(i + 1, line.to_owned())),
+            };
+
+            if !try!(self.set(key, unquote(value))) {
+                return Err(ConfigError::UnknownKey(i + 1, key.to_owned()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply `WORKLOG_*` overrides from a list of environment variables
+    pub fn apply_env<I>(&mut self, vars: I) -> Result<(), ConfigError>
+        where I: Iterator<Item = (String, String)>
+    {
+        for (key, value) in vars {
+            if key.starts_with(ENV_PREFIX) {
+                let key = key[ENV_PREFIX.len()..].to_lowercase();
+                // Unknown WORKLOG_ variables aren't necessarily meant for us
+                let _ = try!(self.set(&key, &value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Set a single key. Returns false if the key isn't known.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<bool, ConfigError> {
+        let invalid =
+            || ConfigError::InvalidValue(key.to_owned(), value.to_owned());
+        match key {
+            "week_start" => {
+                self.week_start = try!(value.parse().map_err(|_| invalid()));
+            }
+            "pay_period" => {
+                self.pay_period = try!(value.parse().map_err(|_| invalid()));
+            }
+            "rounding" => {
+                self.rounding = try!(parsers::parse_rounding(value)
+                                         .map_err(|_| invalid()));
+            }
+            "rounding_scope" => {
+                self.rounding_scope = try!(value.parse().map_err(|_| invalid()));
+            }
+            "punch_rounding" => {
+                let r = try!(parsers::parse_punch_rounding(value).map_err(|_| invalid()));
+                self.punch_rounding_in = r;
+                self.punch_rounding_out = r;
+            }
+            "punch_rounding_in" => {
+                self.punch_rounding_in = try!(parsers::parse_punch_rounding(value)
+                                                  .map_err(|_| invalid()));
+            }
+            "punch_rounding_out" => {
+                self.punch_rounding_out = try!(parsers::parse_punch_rounding(value)
+                                                   .map_err(|_| invalid()));
+            }
+            "overtime_daily" => {
+                self.overtime.daily = try!(parsers::parse_limit(value).map_err(|_| invalid()));
+            }
+            "overtime_daily_double" => {
+                self.overtime.daily_double = try!(parsers::parse_limit(value)
+                                                      .map_err(|_| invalid()));
+            }
+            "overtime_weekly" => {
+                self.overtime.weekly = try!(parsers::parse_limit(value).map_err(|_| invalid()));
+            }
+            "overtime_seventh_day" => {
+                self.overtime.seventh_day = try!(value.parse().map_err(|_| invalid()));
+            }
+            "rate" => self.rates.default = Some(try!(value.parse().map_err(|_| invalid()))),
+            "currency" => self.currency = value.to_owned(),
+            k if k.starts_with("rate.#") && k.len() > 6 => {
+                let rate = try!(value.parse().map_err(|_| invalid()));
+                self.rates.tags.insert(k[6..].to_owned(), rate);
+            }
+            k if k.starts_with("rate.") && k.len() > 5 => {
+                let rate = try!(value.parse().map_err(|_| invalid()));
+                self.rates.projects.insert(k[5..].to_owned(), rate);
+            }
+            "invoice_template" => {
+                self.invoice_template = if value.is_empty() || value == "none" {
+                    None
+                } else {
+                    Some(expand_home(value))
+                };
+            }
+            "invoice_prefix" => self.invoice_prefix = value.to_owned(),
+            k if k.starts_with("tax.") && k.len() > 4 => {
+                let percent: f64 = try!(value.trim_right_matches('%')
+                                            .trim()
+                                            .parse()
+                                            .map_err(|_| invalid()));
+                if !percent.is_finite() {
+                    return Err(invalid());
+                }
+                let name = &k[4..];
+                self.taxes.retain(|&(ref n, _)| n != name);
+                self.taxes.push((name.to_owned(), percent));
+            }
+            "data_file" => {
+                if value.is_empty() {
+                    return Err(invalid());
+                }
+                self.data_file = expand_home(value);
+            }
+            "date_format" => self.date_format = value.to_owned(),
+            "time_format" => self.time_format = value.to_owned(),
+            "memo" => self.memo = value.to_owned(),
+            "double_punch" => {
+                self.double_punch = try!(value.parse().map_err(|_| invalid()));
+            }
+            "day_start" => {
+                self.day_start = try!(parsers::parse_clock_time(value)
+                                          .map_err(|_| invalid()));
+            }
+            "day_end" => {
+                self.day_end = try!(parsers::parse_clock_time(value)
+                                        .map_err(|_| invalid()));
+            }
+            "split_days" => {
+                self.split_days = try!(value.parse().map_err(|_| invalid()));
+            }
+            "day_boundary" => {
+                self.day_boundary = try!(parsers::parse_clock_time(value)
+                                             .map_err(|_| invalid()));
+            }
+            "strict" => {
+                self.strict = try!(value.parse().map_err(|_| invalid()));
+            }
+            "max_interval" => {
+                self.max_interval = try!(parsers::parse_limit(value).map_err(|_| invalid()));
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// How intervals crossing the day boundary are credited
+    pub fn attribution(&self) -> DayAttribution {
+        if self.split_days {
+            DayAttribution::Split(self.day_boundary)
+        } else {
+            DayAttribution::Start
+        }
+    }
+
+    /// Combined date and time format
+    pub fn datetime_format(&self) -> String {
+        format!("{} {}", self.date_format, self.time_format)
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let week_start = format!("{:?}", self.week_start).to_lowercase();
+        try!(writeln!(f, "week_start = {}", week_start));
+        try!(writeln!(f, "pay_period = {}", self.pay_period));
+        try!(writeln!(f, "rounding = {}", self.rounding));
+        try!(writeln!(f, "rounding_scope = {}", self.rounding_scope));
+        for &(key, r) in &[("punch_rounding_in", self.punch_rounding_in),
+                           ("punch_rounding_out", self.punch_rounding_out)] {
+            match r {
+                Some(r) => try!(writeln!(f, "{} = {}", key, r)),
+                None => try!(writeln!(f, "{} = none", key)),
+            }
+        }
+        for &(key, limit) in &[("overtime_daily", self.overtime.daily),
+                               ("overtime_daily_double", self.overtime.daily_double),
+                               ("overtime_weekly", self.overtime.weekly)] {
+            match limit {
+                Some(d) => try!(writeln!(f, "{} = {}h", key, util::hours(d))),
+                None => try!(writeln!(f, "{} = none", key)),
+            }
+        }
+        try!(writeln!(f, "overtime_seventh_day = {}", self.overtime.seventh_day));
+        if let Some(ref rate) = self.rates.default {
+            try!(writeln!(f, "rate = {}", rate));
+        }
+        for (project, rate) in &self.rates.projects {
+            try!(writeln!(f, "rate.{} = {}", project, rate));
+        }
+        for (tag, rate) in &self.rates.tags {
+            try!(writeln!(f, "rate.#{} = {}", tag, rate));
+        }
+        try!(writeln!(f, "currency = \"{}\"", self.currency));
+        match self.invoice_template {
+            Some(ref path) => try!(writeln!(f, "invoice_template = {}", path.display())),
+            None => try!(writeln!(f, "invoice_template = none")),
+        }
+        try!(writeln!(f, "invoice_prefix = \"{}\"", self.invoice_prefix));
+        for &(ref name, percent) in &self.taxes {
+            try!(writeln!(f, "tax.{} = {}%", name, percent));
+        }
+        try!(writeln!(f, "data_file = {}", self.data_file.display()));
+        try!(writeln!(f, "date_format = \"{}\"", self.date_format));
+        try!(writeln!(f, "time_format = \"{}\"", self.time_format));
+        try!(writeln!(f, "memo = \"{}\"", self.memo));
+        try!(writeln!(f, "double_punch = {}", self.double_punch));
+        try!(writeln!(f, "day_start = {}", self.day_start.format("%H:%M:%S")));
+        try!(writeln!(f, "day_end = {}", self.day_end.format("%H:%M:%S")));
+        try!(writeln!(f, "split_days = {}", self.split_days));
+        try!(writeln!(f,
+                      "day_boundary = {}",
+                      self.day_boundary.format("%H:%M:%S")));
+        try!(writeln!(f, "strict = {}", self.strict));
+        match self.max_interval {
+            Some(d) => writeln!(f, "max_interval = {}h", util::hours(d)),
+            None => writeln!(f, "max_interval = none"),
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/worklog/config`, falling back to `~/.config`
+pub fn default_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(ref p) if !p.is_empty() => Some(PathBuf::from(p)),
+        _ => env::home_dir().map(|h| h.join(".config")),
+    };
+    base.map(|b| b.join("worklog").join("config"))
+}
+
+fn unquote(s: &str) -> &str {
+    if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+        &s[1..s.len() - 1]
+    } else {
+        s
+    }
+}
+
+fn expand_home(s: &str) -> PathBuf {
+    if s.starts_with("~/") {
+        if let Some(home) = env::home_dir() {
+            return home.join(&s[2..]);
+        }
+    }
+    PathBuf::from(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn read_str_test() {
+        let mut config = Config::new().unwrap();
+        let s = "# comment\n\
+                 ; another comment\n\
+                 [worklog]\n\
+                 \n\
+                 week_start = mon\n\
+                 pay_period = biweekly 2017-01-06\n\
+                 rounding = -30m\n\
+                 rounding_scope = Week\n\
+                 punch_rounding = =15m,threshold=7m\n\
+                 punch_rounding_out = -6m\n\
+                 overtime_daily = 8h\n\
+                 overtime_daily_double = 12h\n\
+                 overtime_weekly = none\n\
+                 overtime_seventh_day = true\n\
+                 rate = 50, 60 from 2017-06-01\n\
+                 rate.acme = 75\n\
+                 rate.#urgent = 100\n\
+                 currency = €\n\
+                 invoice_template = /tmp/invoice.html\n\
+                 invoice_prefix = INV-\n\
+                 tax.GST = 5%\n\
+                 tax.PST = 7\n\
+                 data_file = /tmp/worklog.csv\n\
+                 date_format = \"%d/%m/%Y\"\n\
+                 time_format=%H:%M\n\
+                 memo = \"\"\n\
+                 double_punch = Reject\n\
+                 day_start = 8:30\n\
+                 day_end = 4:30pm\n\
+                 split_days = true\n\
+                 day_boundary = 4:00\n\
+                 strict = true\n\
+                 max_interval = 12h";
+        config.read_str(s).unwrap();
+        assert_eq!(config.week_start, Weekday::Mon);
+        assert_eq!(config.pay_period,
+                   PayPeriod::BiWeekly(NaiveDate::from_ymd(2017, 1, 6)));
+        assert_eq!(config.rounding, Rounding::Down(Duration::seconds(1800)));
+        assert_eq!(config.rounding_scope, RoundingScope::Week);
+        assert_eq!(config.punch_rounding_in,
+                   Some(PunchRounding {
+                            increment: Duration::minutes(15),
+                            threshold: Duration::minutes(7),
+                        }));
+        assert_eq!(config.punch_rounding_out,
+                   Some(PunchRounding::new(Rounding::Down(Duration::minutes(6)), None)));
+        assert_eq!(config.overtime,
+                   OvertimeRules {
+                       daily: Some(Duration::hours(8)),
+                       daily_double: Some(Duration::hours(12)),
+                       weekly: None,
+                       seventh_day: true,
+                   });
+        assert_eq!(config.rates.default, Some("50, 60 from 2017-06-01".parse().unwrap()));
+        assert_eq!(config.rates.projects["acme"], "75".parse().unwrap());
+        assert_eq!(config.rates.tags["urgent"], "100".parse().unwrap());
+        assert_eq!(config.currency, "€");
+        assert_eq!(config.invoice_template, Some(PathBuf::from("/tmp/invoice.html")));
+        assert_eq!(config.invoice_prefix, "INV-");
+        assert_eq!(config.taxes,
+                   vec![(String::from("GST"), 5.0), (String::from("PST"), 7.0)]);
+        assert_eq!(config.data_file, PathBuf::from("/tmp/worklog.csv"));
+        assert_eq!(config.date_format, "%d/%m/%Y");
+        assert_eq!(config.time_format, "%H:%M");
+        assert_eq!(config.datetime_format(), "%d/%m/%Y %H:%M");
+        assert_eq!(config.memo, "");
+        assert_eq!(config.double_punch, PunchPolicy::Reject);
+        assert_eq!(config.day_start, NaiveTime::from_hms(8, 30, 0));
+        assert_eq!(config.day_end, NaiveTime::from_hms(16, 30, 0));
+        assert_eq!(config.attribution(),
+                   DayAttribution::Split(NaiveTime::from_hms(4, 0, 0)));
+        assert!(config.strict);
+        assert_eq!(config.max_interval, Some(Duration::hours(12)));
+
+        // Display output can be read back in
+        let mut copy = Config::new().unwrap();
+        copy.read_str(&config.to_string()).unwrap();
+        assert_eq!(copy, config);
+    }
+
+    #[test]
+    fn read_str_error_test() {
+        let mut config = Config::new().unwrap();
+        match config.read_str("week_start = mon\nbogus") {
+            Err(ConfigError::Syntax(2, _)) => {}
+            e => panic!("{:?}", e),
+        }
+        match config.read_str("colour = blue") {
+            Err(ConfigError::UnknownKey(1, _)) => {}
+            e => panic!("{:?}", e),
+        }
+        match config.read_str("week_start = caturday") {
+            Err(ConfigError::InvalidValue(_, _)) => {}
+            e => panic!("{:?}", e),
+        }
+        match config.read_str("double_punch = maybe") {
+            Err(ConfigError::InvalidValue(_, _)) => {}
+            e => panic!("{:?}", e),
+        }
+        match config.read_str("rounding = up") {
+            Err(ConfigError::InvalidValue(_, _)) => {}
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn apply_env_test() {
+        let mut config = Config::new().unwrap();
+        let vars = vec![("WORKLOG_WEEK_START".to_owned(), "sun".to_owned()),
+                        ("WORKLOG_MEMO".to_owned(), "Overridden".to_owned()),
+                        ("WORKLOG_UNRELATED".to_owned(), "x".to_owned()),
+                        ("HOME".to_owned(), "/nowhere".to_owned())];
+        config.apply_env(vars.into_iter()).unwrap();
+        assert_eq!(config.week_start, Weekday::Sun);
+        assert_eq!(config.memo, "Overridden");
+    }
+}
@@ -1,5 +1,8 @@
 use chrono;
+use config::ConfigError;
+use invoice::InvoiceError;
 use parsers::ParseError;
+use serde_json;
 use std::env;
 use std::error::Error;
 use std::fmt;
@@ -13,6 +16,11 @@ pub enum WorklogError {
     CronoParse(chrono::ParseError),
     TimeClock(TimeClockError),
     ParseError(ParseError),
+    Config(ConfigError),
+    Json(serde_json::Error),
+    Invoice(InvoiceError),
+    /// `check` found this many problems
+    Problems(usize),
 }
 
 impl From<ParseError> for WorklogError {
@@ -21,6 +29,24 @@ impl From<ParseError> for WorklogError {
     }
 }
 
+impl From<ConfigError> for WorklogError {
+    fn from(err: ConfigError) -> WorklogError {
+        WorklogError::Config(err)
+    }
+}
+
+impl From<serde_json::Error> for WorklogError {
+    fn from(err: serde_json::Error) -> WorklogError {
+        WorklogError::Json(err)
+    }
+}
+
+impl From<InvoiceError> for WorklogError {
+    fn from(err: InvoiceError) -> WorklogError {
+        WorklogError::Invoice(err)
+    }
+}
+
 impl From<env::VarError> for WorklogError {
     fn from(err: env::VarError) -> WorklogError {
         WorklogError::Env(err)
@@ -57,6 +83,10 @@ impl Error for WorklogError {
             WorklogError::CronoParse(ref err) => err.description(),
             WorklogError::TimeClock(ref err) => err.description(),
             WorklogError::ParseError(ref err) => err.description(),
+            WorklogError::Config(ref err) => err.description(),
+            WorklogError::Json(ref err) => err.description(),
+            WorklogError::Invoice(ref err) => err.description(),
+            WorklogError::Problems(_) => "Problems found in the log",
         }
     }
 
@@ -67,6 +97,10 @@ impl Error for WorklogError {
             WorklogError::CronoParse(ref err) => Some(err as &Error),
             WorklogError::TimeClock(ref err) => Some(err as &Error),
             WorklogError::ParseError(ref err) => Some(err as &Error),
+            WorklogError::Config(ref err) => Some(err as &Error),
+            WorklogError::Json(ref err) => Some(err as &Error),
+            WorklogError::Invoice(ref err) => Some(err as &Error),
+            WorklogError::Problems(_) => None,
         }
     }
 }
@@ -80,6 +114,11 @@ impl fmt::Display for WorklogError {
             WorklogError::CronoParse(ref err) => fmt::Display::fmt(err, f),
             WorklogError::TimeClock(ref err) => fmt::Display::fmt(err, f),
             WorklogError::ParseError(ref err) => fmt::Display::fmt(err, f),
+            WorklogError::Config(ref err) => fmt::Display::fmt(err, f),
+            WorklogError::Json(ref err) => fmt::Display::fmt(err, f),
+            WorklogError::Invoice(ref err) => fmt::Display::fmt(err, f),
+            WorklogError::Problems(1) => write!(f, "Found 1 problem in the log"),
+            WorklogError::Problems(n) => write!(f, "Found {} problems in the log", n),
         }
     }
 }
@@ -0,0 +1,513 @@
+// Invoices.
+//
+// An invoice is a list of line items, per day or per project, with the tax
+// on their subtotal. It's rendered by filling in a template, where
+// `{{number}}`, `{{date}}`, `{{start}}`, `{{end}}`, `{{items}}`,
+// `{{hours}}`, `{{subtotal}}`, `{{taxes}}` and `{{total}}` are replaced.
+// Items and tax lines are rendered as plain text, Markdown table rows or
+// HTML table rows to suit the template.
+
+use chrono::prelude::*;
+use chrono::Duration;
+use rates::{self, Charge, Rates};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use timeclock::DateRecord;
+use util::{self, Rounding, RoundingScope};
+
+#[derive(Debug)]
+pub enum InvoiceError {
+    /// Items by day can't be rounded by the week or period
+    Scope(RoundingScope),
+    /// The invoice counter file doesn't hold a number
+    Counter(PathBuf, String),
+    /// Time without a rate, as the date, project and tags
+    Unrated(Vec<String>),
+}
+
+impl Error for InvoiceError {
+    fn description(&self) -> &str {
+        match *self {
+            InvoiceError::Scope(_) => "Items by day can only be rounded by pair or day",
+            InvoiceError::Counter(..) => "The last invoice number isn't a number",
+            InvoiceError::Unrated(_) => "No rate for some of the time",
+        }
+    }
+}
+
+impl fmt::Display for InvoiceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InvoiceError::Scope(scope) => {
+                write!(f,
+                       "Items by day can't be rounded by the {}, use --by project or \
+                        --round-scope pair or day",
+                       scope)
+            }
+            InvoiceError::Counter(ref path, ref content) => {
+                write!(f,
+                       "{}: the last invoice number isn't a number: '{}'",
+                       path.display(),
+                       content)
+            }
+            InvoiceError::Unrated(ref times) => {
+                write!(f, "No rate for the time on {}", times.join(", "))
+            }
+        }
+    }
+}
+
+/// How the items and taxes are rendered
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Style {
+    Text,
+    Markdown,
+    Html,
+}
+
+/// Time charged at one rate
+#[derive(Clone,Debug,PartialEq)]
+pub struct LineItem {
+    /// None for per project items
+    pub date: Option<Date<FixedOffset>>,
+    pub description: String,
+    pub duration: Duration,
+    pub rate: f64,
+}
+
+impl LineItem {
+    pub fn hours(&self) -> f64 {
+        util::hours(self.duration)
+    }
+
+    /// Hours times the rate, rounded to cents
+    pub fn amount(&self) -> f64 {
+        rates::cents(self.hours() * self.rate)
+    }
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Invoice {
+    pub number: String,
+    pub date: Date<FixedOffset>,
+    /// The days covered, if there are any
+    pub range: Option<(Date<FixedOffset>, Date<FixedOffset>)>,
+    pub items: Vec<LineItem>,
+    /// Tax names and percentages
+    pub taxes: Vec<(String, f64)>,
+}
+
+impl Invoice {
+    pub fn hours(&self) -> f64 {
+        util::hours(self.items.iter().fold(Duration::zero(), |acc, i| acc + i.duration))
+    }
+
+    pub fn subtotal(&self) -> f64 {
+        rates::cents(self.items.iter().map(|i| i.amount()).sum())
+    }
+
+    /// Each tax with its percentage and amount
+    pub fn tax_amounts(&self) -> Vec<(&str, f64, f64)> {
+        let subtotal = self.subtotal();
+        self.taxes
+            .iter()
+            .map(|&(ref name, percent)| {
+                     (name.as_ref(), percent, rates::cents(subtotal * percent / 100.0))
+                 })
+            .collect()
+    }
+
+    pub fn total(&self) -> f64 {
+        let taxes: f64 = self.tax_amounts().iter().map(|t| t.2).sum();
+        rates::cents(self.subtotal() + taxes)
+    }
+}
+
+/// Add `duration` to the item for `description` at `rate`, or start a new
+/// one
+fn add_item(items: &mut Vec<LineItem>,
+            date: Option<Date<FixedOffset>>,
+            description: &str,
+            duration: Duration,
+            rate: f64) {
+    if duration == Duration::zero() {
+        return;
+    }
+    let existing = items.iter_mut()
+        .find(|i| i.date == date && i.description == description && i.rate == rate);
+    match existing {
+        Some(item) => item.duration = item.duration + duration,
+        None => {
+            items.push(LineItem {
+                           date: date,
+                           description: description.to_owned(),
+                           duration: duration,
+                           rate: rate,
+                       })
+        }
+    }
+}
+
+/// The projects in `charge`, joined for a description
+fn projects(charge: &Charge) -> String {
+    let named: Vec<&str> = charge.projects
+        .iter()
+        .filter(|p| !p.is_empty())
+        .map(|p| p.as_ref())
+        .collect();
+    named.join(", ")
+}
+
+/// Fail if any of the time in `records` has no rate
+fn check_rated(records: &[DateRecord], rates: &Rates) -> Result<(), InvoiceError> {
+    let mut unrated = Vec::new();
+    for rec in records {
+        let date = rec.date().naive_local();
+        for (&(ref project, ref tags), duration) in rec.labelled() {
+            if *duration == Duration::zero() || rates.rate(date, project, tags).is_some() {
+                continue;
+            }
+            let mut time = format!("{} {}",
+                                   date.format("%F"),
+                                   if project.is_empty() { "(none)" } else { project });
+            for tag in tags {
+                time.push_str(" #");
+                time.push_str(tag);
+            }
+            unrated.push(time);
+        }
+    }
+    if unrated.is_empty() {
+        Ok(())
+    } else {
+        Err(InvoiceError::Unrated(unrated))
+    }
+}
+
+/// One item per day and rate, with each rate's time rounded with
+/// `rounding`. The description is the projects and the day's memo. All the
+/// time needs a rate.
+pub fn items_by_day(records: &[DateRecord],
+                    rounding: Rounding,
+                    rates: &Rates)
+                    -> Result<Vec<LineItem>, InvoiceError> {
+    try!(check_rated(records, rates));
+    let mut items = Vec::new();
+    for rec in records {
+        // only empty time is left without a rate
+        for charge in rates.charges(rec, rounding) {
+            let rate = match charge.rate {
+                Some(rate) => rate,
+                None => continue,
+            };
+            let project = projects(&charge);
+            let description = match (project.is_empty(), rec.memo().is_empty()) {
+                (true, _) => rec.memo().to_owned(),
+                (false, true) => project,
+                (false, false) => format!("{}: {}", project, rec.memo()),
+            };
+            add_item(&mut items, Some(rec.date()), &description, charge.duration, rate);
+        }
+    }
+    Ok(items)
+}
+
+/// One item per project and rate, described by the project and the memos
+/// of the days worked on it. Each day's time at each rate is rounded with
+/// `rounding`. All the time needs a rate.
+pub fn items_by_project(records: &[DateRecord],
+                        rounding: Rounding,
+                        rates: &Rates)
+                        -> Result<Vec<LineItem>, InvoiceError> {
+    try!(check_rated(records, rates));
+    let mut items = Vec::new();
+    let mut memos: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for rec in records {
+        // only empty time is left without a rate
+        for charge in rates.charges(rec, rounding) {
+            let rate = match charge.rate {
+                Some(rate) => rate,
+                None => continue,
+            };
+            let project = projects(&charge);
+            add_item(&mut items, None, &project, charge.duration, rate);
+            let m = memos.entry(project).or_insert_with(Vec::new);
+            if charge.duration != Duration::zero() && !rec.memo().is_empty() &&
+               !m.contains(&rec.memo()) {
+                m.push(rec.memo());
+            }
+        }
+    }
+    for item in &mut items {
+        let project = if item.description.is_empty() {
+            "(none)"
+        } else {
+            &item.description
+        };
+        let memos = memos[&item.description].join("; ");
+        item.description = if memos.is_empty() {
+            project.to_owned()
+        } else {
+            format!("{}: {}", project, memos)
+        };
+    }
+    items.sort_by(|a, b| a.description.cmp(&b.description));
+    Ok(items)
+}
+
+/// The built in template for `style`
+pub fn default_template(style: Style) -> &'static str {
+    match style {
+        Style::Text => {
+            "Invoice {{number}}\n\
+             Date: {{date}}\n\
+             Period: {{start}} to {{end}}\n\
+             \n\
+             {{items}}\n\
+             \n\
+             Hours: {{hours}}\n\
+             Subtotal: {{subtotal}}\n\
+             {{taxes}}\n\
+             Total: {{total}}\n"
+        }
+        Style::Markdown => {
+            "# Invoice {{number}}\n\
+             \n\
+             Date: {{date}}  \n\
+             Period: {{start}} to {{end}}\n\
+             \n\
+             | Date | Description | Hours | Rate | Amount |\n\
+             |------|-------------|------:|-----:|-------:|\n\
+             {{items}}\n\
+             | | **Subtotal** | {{hours}} | | {{subtotal}} |\n\
+             {{taxes}}\n\
+             | | **Total** | | | **{{total}}** |\n"
+        }
+        Style::Html => {
+            "<!DOCTYPE html>\n\
+             <html>\n\
+             <head><meta charset=\"utf-8\"><title>Invoice {{number}}</title></head>\n\
+             <body>\n\
+             <h1>Invoice {{number}}</h1>\n\
+             <p>Date: {{date}}<br>Period: {{start}} to {{end}}</p>\n\
+             <table>\n\
+             <tr><th>Date</th><th>Description</th><th>Hours</th><th>Rate</th><th>Amount</th></tr>\n\
+             {{items}}\n\
+             <tr><td></td><th>Subtotal</th><td>{{hours}}</td><td></td><td>{{subtotal}}</td></tr>\n\
+             {{taxes}}\n\
+             <tr><td></td><th>Total</th><td></td><td></td><th>{{total}}</th></tr>\n\
+             </table>\n\
+             </body>\n\
+             </html>\n"
+        }
+    }
+}
+
+/// Escape text for HTML
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Render a table row in `style`
+fn row(cells: &[String], style: Style) -> String {
+    match style {
+        Style::Text => {
+            format!("{:10}  {:40}  {:>7}  {:>10}  {:>12}",
+                    cells[0],
+                    cells[1],
+                    cells[2],
+                    cells[3],
+                    cells[4])
+                .trim_right()
+                .to_owned()
+        }
+        Style::Markdown => {
+            let cells: Vec<String> = cells.iter().map(|c| c.replace('|', "\\|")).collect();
+            format!("| {} |", cells.join(" | "))
+        }
+        Style::Html => {
+            let cells: Vec<String> =
+                cells.iter().map(|c| format!("<td>{}</td>", escape(c))).collect();
+            format!("<tr>{}</tr>", cells.join(""))
+        }
+    }
+}
+
+/// Fill in `template` for `invoice`
+pub fn render(invoice: &Invoice,
+              template: &str,
+              style: Style,
+              date_format: &str,
+              currency: &str)
+              -> String {
+    let money = |amount| rates::format_amount(amount, currency);
+    let items: Vec<String> = invoice.items
+        .iter()
+        .map(|i| {
+            let date = i.date.map_or(String::new(), |d| d.format(date_format).to_string());
+            row(&[date,
+                  i.description.clone(),
+                  format!("{:.2}", i.hours()),
+                  money(i.rate),
+                  money(i.amount())],
+                style)
+        })
+        .collect();
+    let taxes: Vec<String> = invoice.tax_amounts()
+        .iter()
+        .map(|&(name, percent, amount)| match style {
+                 Style::Text => format!("{} ({}%): {}", name, percent, money(amount)),
+                 _ => {
+                     row(&[String::new(),
+                           format!("{} ({}%)", name, percent),
+                           String::new(),
+                           String::new(),
+                           money(amount)],
+                         style)
+                 }
+             })
+        .collect();
+    let (start, end) = match invoice.range {
+        Some((start, end)) => {
+            (start.format(date_format).to_string(), end.format(date_format).to_string())
+        }
+        None => (String::new(), String::new()),
+    };
+    let text = |s: String| if style == Style::Html { escape(&s) } else { s };
+
+    template.replace("{{number}}", &text(invoice.number.clone()))
+        .replace("{{date}}", &text(invoice.date.format(date_format).to_string()))
+        .replace("{{start}}", &text(start))
+        .replace("{{end}}", &text(end))
+        .replace("{{items}}", &items.join("\n"))
+        .replace("{{hours}}", &format!("{:.2}", invoice.hours()))
+        .replace("{{subtotal}}", &text(money(invoice.subtotal())))
+        .replace("{{taxes}}", &taxes.join("\n"))
+        .replace("{{total}}", &text(money(invoice.total())))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use timeclock::{Direction, TimeEntry};
+
+    fn record(day: u32, project: &str, minutes: i64, memo: &str) -> DateRecord {
+        let time = FixedOffset::west(6 * 3600).ymd(2017, 1, day).and_hms(9, 0, 0);
+        let mut start = TimeEntry::new(Direction::In, time, memo);
+        start.project = project.to_owned();
+        let end = TimeEntry::new(Direction::Out, time + Duration::minutes(minutes), "");
+        DateRecord::from_time_entries(&start, &end)
+    }
+
+    fn rates() -> Rates {
+        let mut rates = Rates::new();
+        rates.default = Some("50".parse().unwrap());
+        rates.projects.insert(String::from("acme"), "80".parse().unwrap());
+        rates
+    }
+
+    fn invoice() -> Invoice {
+        let records = vec![record(2, "acme", 90, "Design"), record(3, "", 60, "")];
+        Invoice {
+            number: String::from("INV-0007"),
+            date: FixedOffset::west(6 * 3600).ymd(2017, 1, 31),
+            range: Some((records[0].date(), records[1].date())),
+            items: items_by_day(&records, Rounding::None, &rates()).unwrap(),
+            taxes: vec![(String::from("GST"), 5.0)],
+        }
+    }
+
+    #[test]
+    fn items_test() {
+        let mut day = record(2, "acme", 90, "Design");
+        day.add_duration(Duration::minutes(30));
+        let records = vec![day, record(3, "acme", 60, "Build"), record(4, "acme", 0, "Idle")];
+
+        let items = items_by_day(&records, Rounding::None, &rates()).unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].description, "Design");
+        assert_eq!(items[0].amount(), 25.0);
+        assert_eq!(items[1].description, "acme: Design");
+        assert_eq!(items[1].amount(), 120.0);
+
+        let items = items_by_project(&records, Rounding::None, &rates()).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].description, "(none): Design");
+        assert_eq!(items[1].description, "acme: Design; Build");
+        assert_eq!(items[1].hours(), 2.5);
+        assert_eq!(items[1].date, None);
+    }
+
+    #[test]
+    fn unrated_test() {
+        let mut rates = Rates::new();
+        rates.projects.insert(String::from("acme"), "80".parse().unwrap());
+        let mut day = record(2, "", 60, "");
+        day.add(&record(2, "acme", 60, ""));
+        let records = vec![day, record(3, "beta", 30, ""), record(4, "beta", 0, "")];
+        match items_by_day(&records, Rounding::None, &rates) {
+            Err(InvoiceError::Unrated(times)) => {
+                assert_eq!(times, vec!["2017-01-02 (none)", "2017-01-03 beta"])
+            }
+            e => panic!("{:?}", e),
+        }
+        assert!(items_by_project(&records[1..], Rounding::None, &rates).is_err());
+        // no time, no rate needed
+        assert_eq!(items_by_project(&records[2..], Rounding::None, &rates).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn rate_rounding_test() {
+        // untagged and tagged acme time share the acme rate, so they're
+        // rounded together
+        let mut day = record(2, "acme", 35, "");
+        let time = FixedOffset::west(6 * 3600).ymd(2017, 1, 2).and_hms(13, 0, 0);
+        let mut start = TimeEntry::new(Direction::In, time, "");
+        start.project = String::from("acme");
+        start.tags = vec![String::from("misc")];
+        let end = TimeEntry::new(Direction::Out, time + Duration::minutes(35), "");
+        day.add(&DateRecord::from_time_entries(&start, &end));
+        let rounding = Rounding::Up(Duration::minutes(15));
+
+        let items = items_by_day(&[day.clone()], rounding, &rates()).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].hours(), 1.25);
+        assert_eq!(items[0].amount(), 100.0);
+        let items = items_by_project(&[day], rounding, &rates()).unwrap();
+        assert_eq!(items[0].hours(), 1.25);
+    }
+
+    #[test]
+    fn totals_test() {
+        let invoice = invoice();
+        assert_eq!(invoice.hours(), 2.5);
+        assert_eq!(invoice.subtotal(), 170.0);
+        assert_eq!(invoice.tax_amounts(), vec![("GST", 5.0, 8.5)]);
+        assert_eq!(invoice.total(), 178.5);
+    }
+
+    #[test]
+    fn render_test() {
+        let invoice = invoice();
+        let text = render(&invoice, default_template(Style::Text), Style::Text, "%F", "$");
+        assert!(text.starts_with("Invoice INV-0007\nDate: 2017-01-31\n\
+                                  Period: 2017-01-02 to 2017-01-03\n"));
+        assert!(text.contains("2017-01-02  acme: Design"));
+        assert!(text.contains("GST (5%): $8.50\nTotal: $178.50\n"));
+
+        let md = render(&invoice,
+                        default_template(Style::Markdown),
+                        Style::Markdown,
+                        "%F",
+                        "$");
+        assert!(md.contains("| 2017-01-02 | acme: Design | 1.50 | $80.00 | $120.00 |"));
+        assert!(md.contains("|  | GST (5%) |  |  | $8.50 |"));
+
+        let html = render(&invoice, "{{items}}", Style::Html, "%F", "<$>");
+        assert!(html.starts_with("<tr><td>2017-01-02</td><td>acme: Design</td>\
+                                  <td>1.50</td><td>&lt;$&gt;80.00</td>"));
+    }
+}
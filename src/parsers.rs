@@ -7,11 +7,13 @@ use chrono::LocalResult;
 use chrono::prelude::*;
 use std::error::Error;
 use std::fmt;
-use util::Rounding;
+use util;
+use util::{PunchRounding, Rounding};
 
 #[derive(Debug)]
 pub enum ParseError {
-    PE(grammar::ParseError),
+    /// The input and where it failed to parse
+    PE(String, grammar::ParseError),
     Overflow,
     Hour,
     Minute,
@@ -21,16 +23,57 @@ pub enum ParseError {
     Ambiguous,
 }
 
-impl From<grammar::ParseError> for ParseError {
-    fn from(err: grammar::ParseError) -> ParseError {
-        ParseError::PE(err)
+impl ParseError {
+    /// The tokens that would have been accepted, sorted
+    pub fn expected(&self) -> Vec<&'static str> {
+        match *self {
+            ParseError::PE(_, ref err) => {
+                let mut expected: Vec<_> = err.expected.iter().cloned().collect();
+                expected.sort();
+                expected
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The input with a caret under the bad character, for syntax errors
+    pub fn caret(&self) -> Option<String> {
+        match *self {
+            ParseError::PE(ref input, ref err) => {
+                let pad: String = input[..err.offset]
+                    .chars()
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                Some(format!("{}\n{}^", input, pad))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Attach the input to a grammar error
+fn syntax<T>(input: &str, res: grammar::ParseResult<T>) -> Result<T, ParseError> {
+    res.map_err(|err| ParseError::PE(input.to_owned(), err))
+}
+
+/// Keep whichever error got further into the input, or both sets of
+/// expected tokens if they failed at the same place
+fn furthest(a: grammar::ParseError, b: grammar::ParseError) -> grammar::ParseError {
+    if a.offset > b.offset {
+        a
+    } else if b.offset > a.offset {
+        b
+    } else {
+        let mut a = a;
+        a.expected.extend(b.expected);
+        a
     }
 }
 
 impl Error for ParseError {
     fn description(&self) -> &str {
         match *self {
-            ParseError::PE(ref err) => err.description(),
+            ParseError::PE(..) => "Invalid date or time",
             ParseError::Overflow => "Invalid input",
             ParseError::Hour => "The specified hours is invalid",
             ParseError::Minute => "The specified minutes is invalid",
@@ -43,7 +86,7 @@ impl Error for ParseError {
 
     fn cause(&self) -> Option<&Error> {
         match *self {
-            ParseError::PE(ref err) => Some(err as &Error),
+            ParseError::PE(_, ref err) => Some(err as &Error),
             _ => None,
         }
     }
@@ -52,31 +95,174 @@ impl Error for ParseError {
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            ParseError::PE(ref err) => fmt::Display::fmt(err, f),
+            ParseError::PE(ref input, ref err) => {
+                let found = input[err.offset..].chars().next();
+                match found {
+                    Some(c) => {
+                        try!(write!(f, "Unexpected '{}' at column {} of '{}'", c, err.column, input))
+                    }
+                    None => try!(write!(f, "Unexpected end of '{}'", input)),
+                }
+                let expected: Vec<_> = self.expected()
+                    .into_iter()
+                    .map(|e| if e == " " { "a space" } else { e })
+                    .collect();
+                match expected.as_slice() {
+                    &[] => Ok(()),
+                    &[one] => write!(f, ", expected {}", one),
+                    many => write!(f, ", expected one of {}", many.join(", ")),
+                }
+            }
             _ => fmt::Display::fmt(self.description(), f),
         }
     }
 }
 
+/// Date ranges that can be given by name, eg. `last-week` or `2017-Q2`
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum NamedRange {
+    Today,
+    Yesterday,
+    ThisWeek,
+    LastWeek,
+    ThisMonth,
+    LastMonth,
+    ThisYear,
+    LastYear,
+    YearToDate,
+    /// Quarter 1-4, of the current year if no year is given
+    Quarter(Option<i32>, u32),
+    /// ISO 8601 week, eg. 2017-W14. ISO weeks always start on Monday.
+    IsoWeek(i32, u32),
+}
+
+/// Days given relative to the current date, eg. `yesterday` or `last fri`
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum RelativeDay {
+    Today,
+    Yesterday,
+    Tomorrow,
+    /// The most recent weekday, today included
+    This(Weekday),
+    /// The most recent weekday before today
+    Last(Weekday),
+    /// The next weekday after today
+    Next(Weekday),
+}
+
+/// First and last day of a month
+fn month_range(year: i32,
+               month: u32,
+               ofst: FixedOffset)
+               -> Result<(Date<FixedOffset>, Date<FixedOffset>), ParseError> {
+    let first = try!(NaiveDate::from_ymd_opt(year, month, 1).ok_or(ParseError::Date));
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    };
+    let next = try!(next.ok_or(ParseError::Date));
+    Ok((Date::from_utc(first, ofst), Date::from_utc(next, ofst) - Duration::days(1)))
+}
+
+/// Resolve a named range to the first and last days it covers. Weeks start
+/// on `week_start`, except for ISO weeks.
+pub fn parse_range(input: &str,
+                   time: DateTime<FixedOffset>,
+                   week_start: Weekday)
+                   -> Result<(Date<FixedOffset>, Date<FixedOffset>), ParseError> {
+    let named = try!(syntax(input, grammar::named_range(input)));
+    let today = time.date();
+    let ofst = *time.offset();
+    let day = Duration::days(1);
+    let week = Duration::days(7);
+
+    match named {
+        NamedRange::Today => Ok((today, today)),
+        NamedRange::Yesterday => Ok((today - day, today - day)),
+        NamedRange::ThisWeek => {
+            let start = util::week_start(today, week_start);
+            Ok((start, start + week - day))
+        }
+        NamedRange::LastWeek => {
+            let start = util::week_start(today, week_start) - week;
+            Ok((start, start + week - day))
+        }
+        NamedRange::ThisMonth => month_range(today.year(), today.month(), ofst),
+        NamedRange::LastMonth => {
+            if today.month() == 1 {
+                month_range(today.year() - 1, 12, ofst)
+            } else {
+                month_range(today.year(), today.month() - 1, ofst)
+            }
+        }
+        NamedRange::ThisYear => {
+            let (start, _) = try!(month_range(today.year(), 1, ofst));
+            let (_, end) = try!(month_range(today.year(), 12, ofst));
+            Ok((start, end))
+        }
+        NamedRange::LastYear => {
+            let (start, _) = try!(month_range(today.year() - 1, 1, ofst));
+            let (_, end) = try!(month_range(today.year() - 1, 12, ofst));
+            Ok((start, end))
+        }
+        NamedRange::YearToDate => {
+            let (start, _) = try!(month_range(today.year(), 1, ofst));
+            Ok((start, today))
+        }
+        NamedRange::Quarter(year, q) => {
+            let year = year.unwrap_or_else(|| today.year());
+            let (start, _) = try!(month_range(year, q * 3 - 2, ofst));
+            let (_, end) = try!(month_range(year, q * 3, ofst));
+            Ok((start, end))
+        }
+        NamedRange::IsoWeek(year, w) => {
+            let monday = try!(NaiveDate::from_isoywd_opt(year, w, Weekday::Mon)
+                                  .ok_or(ParseError::Date));
+            let start = Date::from_utc(monday, ofst);
+            Ok((start, start + week - day))
+        }
+    }
+}
+
 pub fn parse_rounding(fmt: &str) -> Result<Rounding, ParseError> {
-    let res = try!(grammar::rounding(fmt));
+    let res = try!(syntax(fmt, grammar::rounding(fmt)));
     Ok(res)
 }
 
+/// Parse a length of time such as `8h`, or `none`
+pub fn parse_limit(input: &str) -> Result<Option<Duration>, ParseError> {
+    syntax(input, grammar::limit(input))
+}
+
+/// Parse a punch rounding, eg. `=15m,threshold=7m`, or `none`
+pub fn parse_punch_rounding(input: &str) -> Result<Option<PunchRounding>, ParseError> {
+    syntax(input, grammar::punch_rounding(input))
+}
+
+#[allow(dead_code)] // parse_datetime accepts offsets too
 pub fn parse_offset(offset: &str,
                     time: DateTime<FixedOffset>)
                     -> Result<DateTime<FixedOffset>, ParseError> {
-    let offset = try!(grammar::offset(offset));
+    let offset = try!(syntax(offset, grammar::offset(offset)));
     let offset = Duration::seconds(offset as i64);
     time.checked_add_signed(offset).ok_or(ParseError::Overflow)
 
 }
 
+/// Parse a time of day without a date
+pub fn parse_clock_time(input: &str) -> Result<NaiveTime, ParseError> {
+    let (hour, minute, second) = try!(syntax(input, grammar::time(input)));
+    let nanosecond = second.fract() * 1_000_000_000.0;
+    NaiveTime::from_hms_nano_opt(hour, minute, second as u32, nanosecond as u32)
+        .ok_or(ParseError::Hour)
+}
+
 #[allow(dead_code)] // This function may go away if I don't find a use for it soon
 pub fn parse_time(input: &str,
                   time: DateTime<FixedOffset>)
                   -> Result<DateTime<FixedOffset>, ParseError> {
-    let (hour, minute, second) = try!(grammar::time(input));
+    let (hour, minute, second) = try!(syntax(input, grammar::time(input)));
     let nanosecond = second.fract() * 1_000_000_000.0;
     let time = try!(time.with_hour(hour).ok_or(ParseError::Hour));
     let time = try!(time.with_minute(minute).ok_or(ParseError::Minute));
@@ -87,11 +273,21 @@ pub fn parse_time(input: &str,
 }
 
 
+/// Parse an absolute or relative date and time, or an offset. Missing parts
+/// of the date are taken from `time`, relative expressions and offsets are
+/// resolved against it.
 pub fn parse_datetime(input: &str,
                       time: DateTime<FixedOffset>)
                       -> Result<DateTime<FixedOffset>, ParseError> {
-    let (year, month, day, hour, minute, second, tz) =
-        try!(grammar::datetime(input));
+    let (year, month, day, hour, minute, second, tz) = match grammar::datetime(input) {
+        Ok(res) => res,
+        Err(err) => {
+            return match grammar::relative(input) {
+                       Ok(res) => resolve_relative(res, time),
+                       Err(rel_err) => syntax(input, Err(furthest(err, rel_err))),
+                   }
+        }
+    };
 
     let year = year.unwrap_or_else(|| time.year());
     let month = month.unwrap_or_else(|| time.month());
@@ -118,6 +314,47 @@ pub fn parse_datetime(input: &str,
     Ok(time)
 }
 
+/// Resolve a relative date, time and offset against `time`
+fn resolve_relative(relative: (Option<RelativeDay>, Option<(u32, u32, f32)>, Option<f64>),
+                    time: DateTime<FixedOffset>)
+                    -> Result<DateTime<FixedOffset>, ParseError> {
+    let (day, clock, offset) = relative;
+    let today = time.date();
+    let weekday = today.weekday().num_days_from_monday() as i64;
+    let days_back = |w: Weekday| (weekday - w.num_days_from_monday() as i64 + 7) % 7;
+
+    let date = match day {
+        None | Some(RelativeDay::Today) => today,
+        Some(RelativeDay::Yesterday) => today - Duration::days(1),
+        Some(RelativeDay::Tomorrow) => today + Duration::days(1),
+        Some(RelativeDay::This(w)) => today - Duration::days(days_back(w)),
+        Some(RelativeDay::Last(w)) => {
+            let back = days_back(w);
+            today - Duration::days(if back == 0 { 7 } else { back })
+        }
+        Some(RelativeDay::Next(w)) => today + Duration::days(7 - days_back(w)),
+    };
+
+    // A day on its own means midnight, like a date without a time
+    let res = match (day, clock) {
+        (_, Some((hour, minute, second))) => {
+            let nanosecond = second.fract() * 1_000_000_000.0;
+            try!(date.and_hms_nano_opt(hour, minute, second as u32, nanosecond as u32)
+                     .ok_or(ParseError::Hour))
+        }
+        (Some(_), None) => try!(date.and_hms_opt(0, 0, 0).ok_or(ParseError::Hour)),
+        (None, None) => time,
+    };
+
+    match offset {
+        Some(offset) => {
+            res.checked_add_signed(Duration::seconds(offset as i64))
+                .ok_or(ParseError::Overflow)
+        }
+        None => Ok(res),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,22 +362,125 @@ mod tests {
 
     #[test]
     fn parse_rounding_test() {
-        assert_eq!(parse_rounding("+1D").unwrap(), Rounding::Up(86400.0));
-        assert_eq!(parse_rounding("-1d").unwrap(), Rounding::Down(86400.0));
-        assert_eq!(parse_rounding("=1d").unwrap(), Rounding::Half(86400.0));
+        assert_eq!(parse_rounding("+1D").unwrap(), Rounding::Up(Duration::seconds(86400)));
+        assert_eq!(parse_rounding("-1d").unwrap(), Rounding::Down(Duration::seconds(86400)));
+        assert_eq!(parse_rounding("=1d").unwrap(), Rounding::Half(Duration::seconds(86400)));
+
+        assert_eq!(parse_rounding("+1H").unwrap(), Rounding::Up(Duration::seconds(3600)));
+        assert_eq!(parse_rounding("-1h").unwrap(), Rounding::Down(Duration::seconds(3600)));
+        assert_eq!(parse_rounding("=1h").unwrap(), Rounding::Half(Duration::seconds(3600)));
+
+        assert_eq!(parse_rounding("+30M").unwrap(), Rounding::Up(Duration::seconds(1800)));
+        assert_eq!(parse_rounding("-30m").unwrap(), Rounding::Down(Duration::seconds(1800)));
+        assert_eq!(parse_rounding("=30m").unwrap(), Rounding::Half(Duration::seconds(1800)));
+        assert_eq!(parse_rounding("7.5").unwrap(), Rounding::Half(Duration::seconds(450)));
+
+        assert_eq!(parse_rounding("+30S").unwrap(), Rounding::Up(Duration::seconds(30)));
+        assert_eq!(parse_rounding("-30s").unwrap(), Rounding::Down(Duration::seconds(30)));
+        assert_eq!(parse_rounding("30s").unwrap(), Rounding::Half(Duration::seconds(30)));
+
+        let quarter = Duration::minutes(15);
+        let hour = Duration::hours(1);
+        assert_eq!(parse_rounding("+15m,min=1h").unwrap(),
+                   Rounding::Minimum(quarter, Duration::zero(), hour));
+        assert_eq!(parse_rounding("15m, threshold=5m").unwrap(),
+                   Rounding::Threshold(quarter, Duration::minutes(5)));
+        assert_eq!(parse_rounding("-15m,threshold=5m,min=1h").unwrap(),
+                   Rounding::Minimum(quarter, Duration::minutes(5), hour));
+        assert!(parse_rounding("+15m,min=1h,threshold=5m").is_err());
+
+        // Display output parses back the same
+        for r in &["+15m", "-15m", "=15m", "15m,threshold=5m", "+15m,min=1h"] {
+            let r = parse_rounding(r).unwrap();
+            assert_eq!(parse_rounding(&r.to_string()).unwrap(), r);
+        }
+    }
+
+    #[test]
+    fn parse_limit_test() {
+        assert_eq!(parse_limit("8h").unwrap(), Some(Duration::hours(8)));
+        assert_eq!(parse_limit("7.5h").unwrap(), Some(Duration::minutes(450)));
+        assert_eq!(parse_limit("90").unwrap(), Some(Duration::minutes(90)));
+        assert_eq!(parse_limit("none").unwrap(), None);
+        assert!(parse_limit("+8h").is_err());
+    }
+
+    #[test]
+    fn parse_punch_rounding_test() {
+        let quarter = Duration::minutes(15);
+        assert_eq!(parse_punch_rounding("=15m,threshold=7m").unwrap(),
+                   Some(PunchRounding {
+                            increment: quarter,
+                            threshold: Duration::minutes(7),
+                        }));
+        assert_eq!(parse_punch_rounding("+15m").unwrap(),
+                   Some(PunchRounding::new(Rounding::Up(quarter), None)));
+        assert_eq!(parse_punch_rounding("-0.25h, threshold = 450s").unwrap(),
+                   Some(PunchRounding {
+                            increment: quarter,
+                            threshold: Duration::seconds(450),
+                        }));
+        assert_eq!(parse_punch_rounding("None").unwrap(), None);
+        assert!(parse_punch_rounding("=15m,min=7m").is_err());
+
+        // Display output parses back the same
+        let r = parse_punch_rounding("=15m,threshold=7m").unwrap().unwrap();
+        assert_eq!(parse_punch_rounding(&r.to_string()).unwrap(), Some(r));
+    }
+
+    #[test]
+    fn named_range_test() {
+        assert_eq!(grammar::named_range("today").unwrap(), NamedRange::Today);
+        assert_eq!(grammar::named_range("This-Week").unwrap(),
+                   NamedRange::ThisWeek);
+        assert_eq!(grammar::named_range("last month").unwrap(),
+                   NamedRange::LastMonth);
+        assert_eq!(grammar::named_range("YTD").unwrap(),
+                   NamedRange::YearToDate);
+        assert_eq!(grammar::named_range("q3").unwrap(),
+                   NamedRange::Quarter(None, 3));
+        assert_eq!(grammar::named_range("2016-Q4").unwrap(),
+                   NamedRange::Quarter(Some(2016), 4));
+        assert_eq!(grammar::named_range("2017-W14").unwrap(),
+                   NamedRange::IsoWeek(2017, 14));
+        assert!(grammar::named_range("Q5").is_err());
+        assert!(grammar::named_range("next-week").is_err());
+        assert!(grammar::named_range("2017-4-2").is_err());
+    }
+
+    #[test]
+    fn parse_range_test() {
+        // a Wednesday
+        let ctime: DateTime<FixedOffset> =
+            "2017-03-15T15:55:31-05:00".parse().unwrap();
+        let fmt = |r: (Date<FixedOffset>, Date<FixedOffset>)| {
+            format!("{} {}", r.0.format("%F"), r.1.format("%F"))
+        };
+        let range = |s| fmt(parse_range(s, ctime, Weekday::Sat).unwrap());
 
-        assert_eq!(parse_rounding("+1H").unwrap(), Rounding::Up(3600.0));
-        assert_eq!(parse_rounding("-1h").unwrap(), Rounding::Down(3600.0));
-        assert_eq!(parse_rounding("=1h").unwrap(), Rounding::Half(3600.0));
+        assert_eq!(range("today"), "2017-03-15 2017-03-15");
+        assert_eq!(range("yesterday"), "2017-03-14 2017-03-14");
+        assert_eq!(range("this-week"), "2017-03-11 2017-03-17");
+        assert_eq!(range("last-week"), "2017-03-04 2017-03-10");
+        assert_eq!(range("this-month"), "2017-03-01 2017-03-31");
+        assert_eq!(range("last-month"), "2017-02-01 2017-02-28");
+        assert_eq!(range("last-year"), "2016-01-01 2016-12-31");
+        assert_eq!(range("ytd"), "2017-01-01 2017-03-15");
+        assert_eq!(range("Q3"), "2017-07-01 2017-09-30");
+        assert_eq!(range("2016-Q4"), "2016-10-01 2016-12-31");
+        assert_eq!(range("2017-W14"), "2017-04-03 2017-04-09");
+        assert!(parse_range("2017-W54", ctime, Weekday::Sat).is_err());
 
-        assert_eq!(parse_rounding("+30M").unwrap(), Rounding::Up(1800.0));
-        assert_eq!(parse_rounding("-30m").unwrap(), Rounding::Down(1800.0));
-        assert_eq!(parse_rounding("=30m").unwrap(), Rounding::Half(1800.0));
-        assert_eq!(parse_rounding("7.5").unwrap(), Rounding::Half(450.0));
+        let monday = fmt(parse_range("this-week", ctime, Weekday::Mon)
+                             .unwrap());
+        assert_eq!(monday, "2017-03-13 2017-03-19");
 
-        assert_eq!(parse_rounding("+30S").unwrap(), Rounding::Up(30.0));
-        assert_eq!(parse_rounding("-30s").unwrap(), Rounding::Down(30.0));
-        assert_eq!(parse_rounding("30s").unwrap(), Rounding::Half(30.0));
+        // January wraps to the previous year
+        let ctime: DateTime<FixedOffset> =
+            "2017-01-15T15:55:31-05:00".parse().unwrap();
+        assert_eq!(fmt(parse_range("last-month", ctime, Weekday::Sat)
+                           .unwrap()),
+                   "2016-12-01 2016-12-31");
     }
 
     #[test]
@@ -166,10 +506,47 @@ mod tests {
         assert_eq!(grammar::time("9:22:32 Am").unwrap(), (9, 22, 32.0));
         assert_eq!(grammar::time("9:22:32.055Pm").unwrap(), (21, 22, 32.055));
         assert_eq!(grammar::time("9:22 Pm").unwrap(), (21, 22, 0.0));
-        // Might want to fail on these someday
-        assert_eq!(grammar::time("9:22:32. Am").unwrap(), (9, 22, 32.0));
-        assert_eq!(grammar::time("9:22: Pm").unwrap(), (21, 22, 0.0));
-        assert_eq!(grammar::time("99:22:32").unwrap(), (99, 22, 32.0));
+        assert_eq!(grammar::time("12:30 am").unwrap(), (0, 30, 0.0));
+        assert_eq!(grammar::time("5pm").unwrap(), (17, 0, 0.0));
+        assert_eq!(grammar::time("23:59:59.5").unwrap(), (23, 59, 59.5));
+        assert!(grammar::time("9:22:32. Am").is_err());
+        assert!(grammar::time("9:22: Pm").is_err());
+        assert!(grammar::time("99:22:32").is_err());
+        assert!(grammar::time("24:00").is_err());
+        assert!(grammar::time("9:60").is_err());
+        assert!(grammar::time("9:22:60").is_err());
+        assert!(grammar::time("13:00 pm").is_err());
+        assert!(grammar::time("0am").is_err());
+    }
+
+    #[test]
+    fn no_panic_test() {
+        // overflowing numbers are parse errors, not panics
+        assert!(parse_rounding("+1.2.3h").is_err());
+        assert!(grammar::offset("+1.2.3h").is_err());
+        assert!(grammar::named_range("99999-Q1").is_err());
+        assert!(grammar::datetime("99999-1-1").is_err());
+    }
+
+    #[test]
+    fn error_position_test() {
+        let ctime: DateTime<FixedOffset> =
+            "2017-04-30T15:55:31-05:00".parse().unwrap();
+
+        let err = parse_datetime("4/32 9:00", ctime).unwrap_err();
+        assert_eq!(err.expected(), vec!["day (1-31)"]);
+        assert_eq!(err.caret().unwrap(), "4/32 9:00\n  ^");
+
+        let err = parse_datetime("yesterday 9:75", ctime).unwrap_err();
+        assert_eq!(err.expected(), vec!["minutes (00-59)"]);
+        assert_eq!(err.to_string(),
+                   "Unexpected '7' at column 13 of 'yesterday 9:75', \
+                    expected minutes (00-59)");
+
+        let err = parse_datetime("9:30x", ctime).unwrap_err();
+        assert_eq!(err.caret().unwrap(), "9:30x\n    ^");
+
+        assert!(ParseError::Date.caret().is_none());
     }
 
     #[test]
@@ -182,6 +559,15 @@ mod tests {
                    "2017-04-30T09:22:00-05:00");
     }
 
+    #[test]
+    fn parse_clock_time_test() {
+        assert_eq!(parse_clock_time("5:30 pm").unwrap(),
+                   NaiveTime::from_hms(17, 30, 0));
+        assert_eq!(parse_clock_time("09:00").unwrap(),
+                   NaiveTime::from_hms(9, 0, 0));
+        assert!(parse_clock_time("25:00").is_err());
+    }
+
     #[test]
     fn datetime_test() {
         assert_eq!(grammar::datetime("4/2 9:22 Pm").unwrap(),
@@ -221,6 +607,45 @@ mod tests {
                    "2017-04-30T01:24:00-05:00");
     }
 
+    #[test]
+    fn relative_test() {
+        assert_eq!(grammar::relative("now").unwrap(), (None, None, None));
+        assert_eq!(grammar::relative("now-20m").unwrap(),
+                   (None, None, Some(-1200.0)));
+        assert_eq!(grammar::relative("Yesterday 5pm").unwrap(),
+                   (Some(RelativeDay::Yesterday), Some((17, 0, 0.0)), None));
+        assert_eq!(grammar::relative("last friday 09:00").unwrap(),
+                   (Some(RelativeDay::Last(Weekday::Fri)), Some((9, 0, 0.0)), None));
+        assert_eq!(grammar::relative("mon at noon").unwrap(),
+                   (Some(RelativeDay::This(Weekday::Mon)), Some((12, 0, 0.0)), None));
+        assert_eq!(grammar::relative("midnight").unwrap(),
+                   (None, Some((0, 0, 0.0)), None));
+        assert!(grammar::relative("last").is_err());
+        assert!(grammar::relative("someday").is_err());
+    }
+
+    #[test]
+    fn parse_relative_test() {
+        // a Sunday
+        let ctime: DateTime<FixedOffset> =
+            "2017-04-30T15:55:31-05:00".parse().unwrap();
+        let parse = |s| parse_datetime(s, ctime).unwrap().to_rfc3339();
+
+        assert_eq!(parse("now"), "2017-04-30T15:55:31-05:00");
+        assert_eq!(parse("now-20m"), "2017-04-30T15:35:31-05:00");
+        assert_eq!(parse("yesterday 5pm"), "2017-04-29T17:00:00-05:00");
+        assert_eq!(parse("tomorrow"), "2017-05-01T00:00:00-05:00");
+        assert_eq!(parse("last friday 09:00"), "2017-04-28T09:00:00-05:00");
+        assert_eq!(parse("monday 8:30"), "2017-04-24T08:30:00-05:00");
+        assert_eq!(parse("sunday 8:30"), "2017-04-30T08:30:00-05:00");
+        assert_eq!(parse("last sun"), "2017-04-23T00:00:00-05:00");
+        assert_eq!(parse("next sunday noon"), "2017-05-07T12:00:00-05:00");
+        assert_eq!(parse("noon"), "2017-04-30T12:00:00-05:00");
+        assert_eq!(parse("midnight+1h"), "2017-04-30T01:00:00-05:00");
+        assert_eq!(parse("5pm"), "2017-04-30T17:00:00-05:00");
+        assert!(parse_datetime("yesterday 25:00", ctime).is_err());
+    }
+
     #[test]
     #[should_panic(expected = "ParseError")]
     fn parse_datetime_seperator_test() {
@@ -0,0 +1,273 @@
+// Hourly rates.
+//
+// A rate is a list of amounts, each taking effect from a date, eg.
+// `50, 60 from 2017-06-01`. There's a default rate, and rates for projects
+// and tags. Time tagged with a tag that has a rate is charged at that rate,
+// otherwise at the project's rate, otherwise at the default rate.
+
+use chrono::Duration;
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use timeclock::DateRecord;
+use util::{self, Rounding};
+
+/// Amounts per hour and the dates they take effect
+#[derive(Clone,Debug,PartialEq)]
+pub struct Rate {
+    /// Sorted by date, the first one may have no date
+    amounts: Vec<(Option<NaiveDate>, f64)>,
+}
+
+impl Rate {
+    /// The amount in effect on `date`
+    pub fn on(&self, date: NaiveDate) -> Option<f64> {
+        self.amounts
+            .iter()
+            .rev()
+            .find(|&&(from, _)| from.map_or(true, |from| from <= date))
+            .map(|&(_, amount)| amount)
+    }
+}
+
+impl FromStr for Rate {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Rate, ()> {
+        let mut amounts = Vec::new();
+        for part in s.split(',') {
+            let words: Vec<&str> = part.split_whitespace().collect();
+            let (amount, from) = match words.len() {
+                1 => (words[0], None),
+                3 if words[1].to_lowercase() == "from" => {
+                    let from = try!(NaiveDate::parse_from_str(words[2], "%F")
+                                        .map_err(|_| ()));
+                    (words[0], Some(from))
+                }
+                _ => return Err(()),
+            };
+            let amount: f64 = try!(amount.parse().map_err(|_| ()));
+            if !amount.is_finite() {
+                return Err(());
+            }
+            amounts.push((from, amount));
+        }
+        // None sorts first
+        amounts.sort_by_key(|&(from, _)| from);
+        if amounts.iter().skip(1).any(|&(from, _)| from.is_none()) {
+            return Err(());
+        }
+        Ok(Rate { amounts: amounts })
+    }
+}
+
+/// Formats in the same syntax FromStr accepts
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self.amounts
+            .iter()
+            .map(|&(from, amount)| match from {
+                     Some(from) => format!("{} from {}", amount, from.format("%F")),
+                     None => format!("{}", amount),
+                 })
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// The default, per project and per tag rates
+#[derive(Clone,Debug,PartialEq)]
+pub struct Rates {
+    pub default: Option<Rate>,
+    pub projects: BTreeMap<String, Rate>,
+    pub tags: BTreeMap<String, Rate>,
+}
+
+impl Rates {
+    pub fn new() -> Rates {
+        Rates {
+            default: None,
+            projects: BTreeMap::new(),
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// True if no rates are set
+    pub fn is_empty(&self) -> bool {
+        self.default.is_none() && self.projects.is_empty() && self.tags.is_empty()
+    }
+
+    /// The rate for time on `project` with `tags`. The first tag with a rate
+    /// wins, then the project, then the default.
+    pub fn rate(&self, date: NaiveDate, project: &str, tags: &[String]) -> Option<f64> {
+        tags.iter()
+            .filter_map(|t| self.tags.get(t))
+            .chain(self.projects.get(project))
+            .chain(self.default.as_ref())
+            .filter_map(|r| r.on(date))
+            .next()
+    }
+
+    /// The time in `record` grouped by the rate it's charged at, in the
+    /// order the rates first come up. Each rate's time is rounded as a
+    /// whole, however many projects and tags it's spread over.
+    pub fn charges(&self, record: &DateRecord, rounding: Rounding) -> Vec<Charge> {
+        let date = record.date().naive_local();
+        let mut charges: Vec<Charge> = Vec::new();
+        for (&(ref project, ref tags), duration) in record.labelled() {
+            let rate = self.rate(date, project, tags);
+            let i = match charges.iter().position(|c| c.rate == rate) {
+                Some(i) => i,
+                None => {
+                    charges.push(Charge {
+                                     rate: rate,
+                                     projects: Vec::new(),
+                                     duration: Duration::zero(),
+                                 });
+                    charges.len() - 1
+                }
+            };
+            let charge = &mut charges[i];
+            charge.duration = charge.duration + *duration;
+            if !charge.projects.contains(project) {
+                charge.projects.push(project.to_owned());
+            }
+        }
+        for charge in &mut charges {
+            charge.duration = util::round(charge.duration, rounding);
+        }
+        charges
+    }
+
+    /// The earnings for `record` with each rate's time rounded, time without
+    /// a rate earns nothing
+    pub fn earnings(&self, record: &DateRecord, rounding: Rounding) -> f64 {
+        self.charges(record, rounding)
+            .iter()
+            .filter_map(|c| c.rate.map(|rate| rate * util::hours(c.duration)))
+            .sum()
+    }
+}
+
+/// Time charged at one rate
+#[derive(Clone,Debug,PartialEq)]
+pub struct Charge {
+    /// None if no rate applies
+    pub rate: Option<f64>,
+    /// The projects the time was spent on, "" for none
+    pub projects: Vec<String>,
+    pub duration: Duration,
+}
+
+/// An amount of money rounded to cents
+pub fn cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Format an amount of money with two decimals and thousands separators,
+/// eg. `$1,234.50`
+pub fn format_amount(amount: f64, currency: &str) -> String {
+    let cents = (amount.abs() * 100.0).round() as u64;
+    let whole = (cents / 100).to_string();
+    let mut grouped = String::new();
+    for (i, c) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    let sign = if amount < 0.0 && cents > 0 { "-" } else { "" };
+    format!("{}{}{}.{:02}", sign, currency, grouped, cents % 100)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+    use chrono::Duration;
+    use timeclock::{Direction, TimeEntry};
+
+    fn date(d: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(d, "%F").unwrap()
+    }
+
+    #[test]
+    fn rate_test() {
+        let rate: Rate = "60 from 2017-06-01, 50".parse().unwrap();
+        assert_eq!(rate.on(date("2017-01-01")), Some(50.0));
+        assert_eq!(rate.on(date("2017-06-01")), Some(60.0));
+        assert_eq!(rate.to_string(), "50, 60 from 2017-06-01");
+        assert_eq!(rate.to_string().parse(), Ok(rate));
+
+        let later: Rate = "50 from 2017-06-01".parse().unwrap();
+        assert_eq!(later.on(date("2017-01-01")), None);
+
+        assert!("50, 60".parse::<Rate>().is_err());
+        assert!("fifty".parse::<Rate>().is_err());
+        assert!("50 since 2017-06-01".parse::<Rate>().is_err());
+    }
+
+    #[test]
+    fn earnings_test() {
+        let mut rates = Rates::new();
+        rates.default = Some("50".parse().unwrap());
+        rates.projects.insert(String::from("acme"), "75".parse().unwrap());
+        rates.tags.insert(String::from("urgent"), "100".parse().unwrap());
+        let day = date("2017-01-02");
+        let tags = vec![String::from("billable"), String::from("urgent")];
+        assert_eq!(rates.rate(day, "", &[]), Some(50.0));
+        assert_eq!(rates.rate(day, "acme", &tags[..1]), Some(75.0));
+        assert_eq!(rates.rate(day, "acme", &tags), Some(100.0));
+
+        let time = DateTime::parse_from_rfc3339("2017-01-02T09:00:00-06:00").unwrap();
+        let mut start = TimeEntry::new(Direction::In, time, "");
+        start.project = String::from("acme");
+        let end = TimeEntry::new(Direction::Out, time + Duration::minutes(90), "");
+        let mut rec = DateRecord::from_time_entries(&start, &end);
+        rec.add_duration(Duration::hours(1));
+        assert_eq!(rates.earnings(&rec, Rounding::None), 1.5 * 75.0 + 50.0);
+        assert_eq!(Rates::new().earnings(&rec, Rounding::None), 0.0);
+    }
+
+    #[test]
+    fn charges_test() {
+        let mut rates = Rates::new();
+        rates.projects.insert(String::from("acme"), "100".parse().unwrap());
+        let time = DateTime::parse_from_rfc3339("2017-01-02T09:00:00-06:00").unwrap();
+        let pair = |start: DateTime<FixedOffset>, project: &str, tag: Option<&str>| {
+            let mut start = TimeEntry::new(Direction::In, start, "");
+            start.project = String::from(project);
+            start.tags = tag.into_iter().map(String::from).collect();
+            let end = TimeEntry::new(Direction::Out, start.time + Duration::minutes(35), "");
+            DateRecord::from_time_entries(&start, &end)
+        };
+        let mut rec = pair(time, "acme", None);
+        rec.add(&pair(time + Duration::hours(1), "acme", Some("misc")));
+        rec.add(&pair(time + Duration::hours(2), "other", None));
+
+        // both acme sessions are charged at the acme rate, rounded together
+        let rounding = Rounding::Up(Duration::minutes(15));
+        let charges = rates.charges(&rec, rounding);
+        assert_eq!(charges,
+                   vec![Charge {
+                            rate: Some(100.0),
+                            projects: vec![String::from("acme")],
+                            duration: Duration::minutes(75),
+                        },
+                        Charge {
+                            rate: None,
+                            projects: vec![String::from("other")],
+                            duration: Duration::minutes(45),
+                        }]);
+        assert_eq!(rates.earnings(&rec, rounding), 125.0);
+    }
+
+    #[test]
+    fn format_amount_test() {
+        assert_eq!(format_amount(1234.5, "$"), "$1,234.50");
+        assert_eq!(format_amount(0.004, ""), "0.00");
+        assert_eq!(format_amount(-1234567.891, "€"), "-€1,234,567.89");
+        assert_eq!(format_amount(999.999, "$"), "$1,000.00");
+    }
+}
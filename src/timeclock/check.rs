@@ -0,0 +1,200 @@
+// Timesheet checks.
+//
+// Looks for entries that are probably mistakes. Reports pair entries up in
+// time order and quietly make do with whatever they find, so these are the
+// things that would otherwise go unnoticed. Entries are referred to by their
+// index in file order.
+
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::BTreeMap;
+use std::fmt;
+use timeclock::{Direction, TimeEntry};
+
+/// Something wrong with one or more entries
+#[derive(Clone,Debug,PartialEq)]
+pub enum Problem {
+    /// Two Ins or two Outs in a row
+    DoublePunch(usize, usize),
+    /// An Out at or before the In it follows
+    EmptyInterval(usize, usize),
+    /// An interval longer than the maximum
+    LongInterval(usize, usize),
+    /// An entry later than now
+    Future(usize),
+    /// A row earlier than the row before it
+    OutOfOrder(usize, usize),
+    /// Two entries on the same day with different UTC offsets
+    MixedOffsets(usize, usize),
+    /// A row identical to an earlier one
+    Duplicate(usize, usize),
+}
+
+impl Problem {
+    /// The entries involved, in the order they should be shown
+    pub fn rows(&self) -> Vec<usize> {
+        match *self {
+            Problem::Future(i) => vec![i],
+            Problem::DoublePunch(i, j) |
+            Problem::EmptyInterval(i, j) |
+            Problem::LongInterval(i, j) |
+            Problem::OutOfOrder(i, j) |
+            Problem::MixedOffsets(i, j) |
+            Problem::Duplicate(i, j) => vec![i, j],
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Problem::DoublePunch(..) => "Clocked in or out twice in a row",
+            Problem::EmptyInterval(..) => "Clocked out at or before clocking in",
+            Problem::LongInterval(..) => "Clocked in for longer than the maximum",
+            Problem::Future(..) => "Entry is in the future",
+            Problem::OutOfOrder(..) => "Entry is earlier than the row before it",
+            Problem::MixedOffsets(..) => "Entries on the same day have different UTC offsets",
+            Problem::Duplicate(..) => "Entry is a duplicate",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Check `entries`, in file order, for problems. Intervals longer than
+/// `max_interval` and entries after `now` are problems too.
+pub fn check(entries: &[TimeEntry],
+             max_interval: Option<Duration>,
+             now: DateTime<FixedOffset>)
+             -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    let mut by_time: Vec<usize> = (0..entries.len()).collect();
+    by_time.sort_by_key(|&i| entries[i].time);
+
+    // identical entries are next to each other in time order
+    let mut duplicates = vec![false; entries.len()];
+    for (k, &i) in by_time.iter().enumerate() {
+        let first = by_time[..k]
+            .iter()
+            .rev()
+            .take_while(|&&j| entries[j].time == entries[i].time)
+            .filter(|&&j| entries[j] == entries[i])
+            .min();
+        if let Some(&j) = first {
+            duplicates[i] = true;
+            problems.push(Problem::Duplicate(j, i));
+        }
+    }
+
+    // pair up entries the way reports do, leaving out the duplicates
+    let mut prev: Option<usize> = None;
+    for &i in by_time.iter().filter(|&&i| !duplicates[i]) {
+        if let Some(p) = prev {
+            let (a, b) = (&entries[p], &entries[i]);
+            if a.dir == b.dir {
+                problems.push(Problem::DoublePunch(p, i));
+            } else if a.dir == Direction::In {
+                let length = b.time.signed_duration_since(a.time);
+                if length <= Duration::zero() {
+                    problems.push(Problem::EmptyInterval(p, i));
+                } else if max_interval.map_or(false, |max| length > max) {
+                    problems.push(Problem::LongInterval(p, i));
+                }
+            }
+        }
+        prev = Some(i);
+    }
+
+    let mut offsets: BTreeMap<NaiveDate, (usize, bool)> = BTreeMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.time > now {
+            problems.push(Problem::Future(i));
+        }
+        if i > 0 {
+            let before = &entries[i - 1];
+            if entry.time < before.time {
+                problems.push(Problem::OutOfOrder(i - 1, i));
+                // an Out written straight after its In, but earlier
+                if before.dir == Direction::In && entry.dir == Direction::Out {
+                    problems.push(Problem::EmptyInterval(i - 1, i));
+                }
+            }
+        }
+        // the first entry with each offset, once per day
+        let date = entry.time.naive_local().date();
+        let first = offsets.entry(date).or_insert((i, false));
+        if !first.1 && entries[first.0].time.offset() != entry.time.offset() {
+            problems.push(Problem::MixedOffsets(first.0, i));
+            first.1 = true;
+        }
+    }
+
+    problems.sort_by_key(|p| {
+                             let mut rows = p.rows();
+                             rows.sort();
+                             rows
+                         });
+    problems
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use timeclock::{ReadMode, read_with};
+
+    fn entries(s: &str) -> Vec<TimeEntry> {
+        read_with(Cursor::new(s.as_bytes()), ReadMode::Strict).unwrap().entries
+    }
+
+    fn now() -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339("2017-01-10T12:00:00-06:00").unwrap()
+    }
+
+    #[test]
+    fn clean_test() {
+        let e = entries("In,2017-01-02T09:00:00-06:00,\n\
+                         Out,2017-01-02T17:00:00-06:00,\n\
+                         In,2017-01-03T09:00:00-06:00,\n");
+        assert_eq!(check(&e, Some(Duration::hours(16)), now()), vec![]);
+    }
+
+    #[test]
+    fn pairs_test() {
+        let e = entries("In,2017-01-02T09:00:00-06:00,\n\
+                         In,2017-01-02T10:00:00-06:00,\n\
+                         Out,2017-01-02T10:00:00-06:00,\n\
+                         In,2017-01-03T09:00:00-06:00,\n\
+                         Out,2017-01-04T09:00:00-06:00,\n\
+                         In,2017-01-11T09:00:00-06:00,\n");
+        assert_eq!(check(&e, Some(Duration::hours(16)), now()),
+                   vec![Problem::DoublePunch(0, 1),
+                        Problem::EmptyInterval(1, 2),
+                        Problem::LongInterval(3, 4),
+                        Problem::Future(5)]);
+        assert_eq!(check(&e, None, now()).len(), 3);
+    }
+
+    #[test]
+    fn rows_test() {
+        let e = entries("In,2017-01-02T09:00:00-06:00,\n\
+                         Out,2017-01-02T17:00:00-06:00,\n\
+                         In,2017-01-03T09:00:00-06:00,\n\
+                         Out,2017-01-03T08:00:00-06:00,\n\
+                         In,2017-01-02T09:00:00-06:00,\n\
+                         Out,2017-01-05T17:00:00-05:00,\n\
+                         In,2017-01-05T09:00:00-06:00,\n");
+        assert_eq!(check(&e, None, now()),
+                   vec![Problem::Duplicate(0, 4),
+                        // the early Out leaves the entries either side of it
+                        // unpaired
+                        Problem::DoublePunch(1, 3),
+                        Problem::OutOfOrder(2, 3),
+                        Problem::EmptyInterval(2, 3),
+                        Problem::DoublePunch(2, 6),
+                        Problem::OutOfOrder(3, 4),
+                        Problem::OutOfOrder(5, 6),
+                        Problem::MixedOffsets(5, 6)]);
+    }
+}
@@ -1,29 +1,62 @@
 
 
 use chrono::prelude::*;
+use std::collections::BTreeMap;
 use std::fmt;
+use chrono::Duration;
 use timeclock::Combine;
+use timeclock::Direction;
 use timeclock::TimeEntry;
 use timeclock::TimeEntryPair;
+use util;
+use util::Rounding;
 
 
 #[derive(Clone,Debug)]
 pub struct DateRecord {
     date: Date<FixedOffset>,
-    duration: f64,
+    duration: Duration,
     memo: String,
+    projects: BTreeMap<String, Duration>,
+    tags: BTreeMap<String, Duration>,
+    labelled: BTreeMap<(String, Vec<String>), Duration>,
 }
 
 
 impl DateRecord {
     pub fn from_time_entries(start: &TimeEntry, end: &TimeEntry) -> DateRecord {
+        DateRecord::from_time_entries_on(start.time.date(), start, end)
+    }
+
+
+    /// Construct a DateRecord credited to `date` rather than the start date
+    pub fn from_time_entries_on(date: Date<FixedOffset>,
+                                start: &TimeEntry,
+                                end: &TimeEntry)
+                                -> DateRecord {
+        let duration = end.time.signed_duration_since(start.time);
+        // The project is normally set on the In entry
+        let project = if start.project.is_empty() {
+            &end.project
+        } else {
+            &start.project
+        };
         let mut dr = DateRecord {
-            date: start.time.date(),
-            duration: end.time
-                .signed_duration_since(start.time)
-                .num_seconds() as f64,
+            date: date,
+            duration: duration,
             memo: String::new(),
+            projects: BTreeMap::new(),
+            tags: BTreeMap::new(),
+            labelled: BTreeMap::new(),
         };
+        dr.projects.insert(project.to_owned(), duration);
+        let mut tags: Vec<String> = start.tags.iter().chain(end.tags.iter()).cloned().collect();
+        tags.sort();
+        tags.dedup();
+        for tag in &tags {
+            dr.tags.insert(tag.to_owned(), duration);
+        }
+        dr.labelled.insert((project.to_owned(), tags), duration);
         dr.append_memo(&start.memo);
         dr.append_memo(&end.memo);
         dr
@@ -32,31 +65,44 @@ impl DateRecord {
 
     #[allow(dead_code)]
     /// Construct a DateRecord from it's constituent parts
-    pub fn from_parts(date: Date<FixedOffset>, dur: f64, memo: &str) -> Self {
+    pub fn from_parts(date: Date<FixedOffset>, dur: Duration, memo: &str) -> Self {
+        let mut projects = BTreeMap::new();
+        projects.insert(String::new(), dur);
+        let mut labelled = BTreeMap::new();
+        labelled.insert((String::new(), Vec::new()), dur);
         DateRecord {
             date: date,
             duration: dur,
             memo: memo.to_owned(),
+            projects: projects,
+            tags: BTreeMap::new(),
+            labelled: labelled,
         }
     }
 
+    /// Get the exact duration
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+
     #[allow(dead_code)]
     /// Get the duration, expressed in seconds
     pub fn seconds(&self) -> f64 {
-        self.duration
+        util::seconds(self.duration)
     }
 
 
     #[allow(dead_code)]
     /// Get the duration, expressed in minutes
     pub fn minutes(&self) -> f64 {
-        self.duration / 60.0
+        self.seconds() / 60.0
     }
 
 
     /// Get the duration, expressed in hours
     pub fn hours(&self) -> f64 {
-        self.duration / 3600.0
+        util::hours(self.duration)
     }
 
 
@@ -64,10 +110,78 @@ impl DateRecord {
         self.date
     }
 
-    #[allow(dead_code)]
-    // add seconds to the duration
-    pub fn add_seconds(&mut self, secs: f64) {
-        self.duration += secs;
+
+    /// Credit the record to another date
+    pub fn set_date(&mut self, date: Date<FixedOffset>) {
+        self.date = date;
+    }
+
+    // add to the duration
+    pub fn add_duration(&mut self, dur: Duration) {
+        self.duration = self.duration + dur;
+        let project = self.projects.entry(String::new()).or_insert_with(Duration::zero);
+        *project = *project + dur;
+        let unlabelled = self.labelled
+            .entry((String::new(), Vec::new()))
+            .or_insert_with(Duration::zero);
+        *unlabelled = *unlabelled + dur;
+    }
+
+
+    /// Time per project. Time without a project is under "".
+    pub fn projects(&self) -> &BTreeMap<String, Duration> {
+        &self.projects
+    }
+
+
+    /// Time per tag
+    pub fn tags(&self) -> &BTreeMap<String, Duration> {
+        &self.tags
+    }
+
+
+    /// Time per combination of project and tags
+    pub fn labelled(&self) -> &BTreeMap<(String, Vec<String>), Duration> {
+        &self.labelled
+    }
+
+
+    /// True if any of the time was logged against a project or tag
+    pub fn has_labels(&self) -> bool {
+        !self.tags.is_empty() || self.projects.keys().any(|k| !k.is_empty())
+    }
+
+
+    /// A copy with the duration rounded, and the projects and tags scaled
+    /// to match so the projects still add up to the duration. Rates apply to
+    /// parts of the time, so charge from the unrounded record, see
+    /// `Rates::charges`.
+    pub fn rounded(&self, rounding: Rounding) -> DateRecord {
+        self.scaled(util::round(self.duration, rounding))
+    }
+
+
+    /// A copy with the duration changed to `duration`, and the time per
+    /// project, tag and combination of them changed in proportion
+    pub fn scaled(&self, duration: Duration) -> DateRecord {
+        DateRecord {
+            date: self.date,
+            duration: duration,
+            memo: self.memo.clone(),
+            projects: spread(&self.projects, self.duration, duration),
+            tags: scale_all(&self.tags, self.duration, duration),
+            labelled: spread(&self.labelled, self.duration, duration),
+        }
+    }
+
+
+    /// Add the time from `other` regardless of its date. The memo is left
+    /// alone.
+    pub fn add(&mut self, other: &DateRecord) {
+        self.duration = self.duration + other.duration;
+        merge_durations(&mut self.projects, &other.projects);
+        merge_durations(&mut self.tags, &other.tags);
+        merge_durations(&mut self.labelled, &other.labelled);
     }
 
 
@@ -92,7 +206,7 @@ impl DateRecord {
 impl Combine for DateRecord {
     fn combine(&mut self, other: &Self) -> bool {
         if self.date == other.date {
-            self.duration += other.duration;
+            self.add(other);
             self.append_memo(other.memo());
             true
         } else {
@@ -102,6 +216,85 @@ impl Combine for DateRecord {
 }
 
 
+/// Split a pair into one `DateRecord` per day it covers. Days change at
+/// `boundary` rather than midnight, so with a boundary of 04:00 time worked
+/// at 2am counts toward the previous day.
+pub fn split_days(pair: &TimeEntryPair, boundary: NaiveTime) -> Vec<DateRecord> {
+    let shift = boundary.signed_duration_since(NaiveTime::from_hms(0, 0, 0));
+    let end = pair.end();
+    let mut start = pair.start().clone();
+    let mut res = Vec::new();
+    loop {
+        let day = (start.time - shift).date();
+        let next = (day + Duration::days(1)).and_time(boundary);
+        match next {
+            Some(next) if next < end.time => {
+                let mut mid = start.clone();
+                mid.dir = Direction::Out;
+                mid.time = next;
+                mid.memo = String::new();
+                res.push(DateRecord::from_time_entries_on(day, &start, &mid));
+                start.time = next;
+                start.memo = String::new();
+            }
+            _ => {
+                res.push(DateRecord::from_time_entries_on(day, &start, end));
+                return res;
+            }
+        }
+    }
+}
+
+
+/// Add the durations in `other` to `into`, key by key
+fn merge_durations<K: Ord + Clone>(into: &mut BTreeMap<K, Duration>,
+                                   other: &BTreeMap<K, Duration>) {
+    for (k, v) in other {
+        let d = into.entry(k.clone()).or_insert_with(Duration::zero);
+        *d = *d + *v;
+    }
+}
+
+
+/// Scale each of the durations in `durations` by `to / from`, rounding
+/// down to the nanosecond. They're left alone if `from` is zero.
+fn scale_all<K: Ord + Clone>(durations: &BTreeMap<K, Duration>,
+                             from: Duration,
+                             to: Duration)
+                             -> BTreeMap<K, Duration> {
+    let (from, to) = match (from.num_nanoseconds(), to.num_nanoseconds()) {
+        (Some(f), Some(t)) if f != 0 => (f as i128, t as i128),
+        _ => return durations.clone(),
+    };
+    durations.iter()
+        .map(|(k, v)| {
+                 let n = v.num_nanoseconds().map_or(0, |n| n as i128 * to / from);
+                 (k.clone(), Duration::nanoseconds(n as i64))
+             })
+        .collect()
+}
+
+
+/// Scale `durations`, which add up to `from`, so they add up to `to`. What's
+/// lost rounding each one down goes to the largest.
+fn spread<K: Ord + Clone>(durations: &BTreeMap<K, Duration>,
+                          from: Duration,
+                          to: Duration)
+                          -> BTreeMap<K, Duration> {
+    if from == Duration::zero() {
+        return durations.clone();
+    }
+    let mut scaled = scale_all(durations, from, to);
+    let sum = scaled.values().fold(Duration::zero(), |acc, d| acc + *d);
+    let largest = scaled.iter().max_by_key(|&(_, d)| *d).map(|(k, _)| k.clone());
+    if let Some(k) = largest {
+        let d = scaled.get_mut(&k).unwrap();
+        *d = *d + to - sum;
+    }
+    scaled
+}
+
+
 impl fmt::Display for DateRecord {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let date = self.date.format("%F");
@@ -120,25 +313,49 @@ impl From<TimeEntryPair> for DateRecord {
 mod tests {
     use super::*;
     use timeclock::Combine;
+    use timeclock::TimeEntryPair;
 
     const DURATION: f64 = 4321.098765;
 
+    fn duration() -> Duration {
+        Duration::microseconds(4321_098765)
+    }
+
     fn daterecord_helper(date: &str) -> DateRecord {
         let x = NaiveDate::parse_from_str(date, "%F").unwrap();
         let y = FixedOffset::west(6 * 3600).from_local_date(&x).unwrap();
-        DateRecord::from_parts(y, DURATION, "Test")
+        DateRecord::from_parts(y, duration(), "Test")
     }
 
     #[test]
     fn duration_methods_test() {
         let mut dr = daterecord_helper("2017-01-07");
         // check math
+        assert!(dr.duration() == duration());
         assert!(dr.seconds() == DURATION);
         assert!(dr.minutes() == DURATION / 60.0);
         assert!(dr.hours() == DURATION / 3600.0);
         // check that addition works
-        dr.add_seconds(1357.246);
-        assert!(dr.seconds() == DURATION + 1357.246);
+        dr.add_duration(Duration::milliseconds(1357_246));
+        assert!(dr.duration() == Duration::microseconds(5678_344765));
+    }
+
+    #[test]
+    fn sub_second_test() {
+        // fractional seconds on the entries aren't truncated
+        let start = DateTime::parse_from_rfc3339("2017-01-07T09:00:00.25-06:00")
+            .unwrap();
+        let end = DateTime::parse_from_rfc3339("2017-01-07T09:00:01-06:00")
+            .unwrap();
+        let mut dr = DateRecord::from_time_entries(&TimeEntry::new(Direction::In, start, ""),
+                                                   &TimeEntry::new(Direction::Out, end, ""));
+        assert_eq!(dr.duration(), Duration::milliseconds(750));
+        for _ in 0..3 {
+            let other = dr.clone();
+            dr.combine(&other);
+        }
+        assert_eq!(dr.duration(), Duration::seconds(6));
+        assert_eq!(dr.projects()[""], Duration::seconds(6));
     }
 
     #[test]
@@ -160,7 +377,7 @@ mod tests {
         let success = a.combine(&b);
         assert!(success);
         assert_eq!(a.date(), a.date());
-        assert_eq!(a.seconds(), DURATION * 2.0);
+        assert_eq!(a.duration(), duration() * 2);
         assert_eq!(a.memo(), "Test, Test");
 
         // a and c have different dates
@@ -168,7 +385,7 @@ mod tests {
         let success = a.combine(&c);
         assert!(!success);
         assert_eq!(a.date(), a.date());
-        assert_eq!(a.seconds(), DURATION * 2.0);
+        assert_eq!(a.duration(), duration() * 2);
         assert_eq!(a.memo(), "Test, Test");
     }
 
@@ -179,7 +396,115 @@ mod tests {
         println!("{:?}", dr);
         assert!(s ==
                 "DateRecord { date: 2017-01-07-06:00, \
-                duration: 4321.098765, memo: \"Test\" }")
+                duration: Duration { secs: 4321, nanos: 98765000 }, \
+                memo: \"Test\", \
+                projects: {\"\": Duration { secs: 4321, nanos: 98765000 }}, \
+                tags: {}, \
+                labelled: {(\"\", []): Duration { secs: 4321, nanos: 98765000 }} }")
+    }
+
+    fn pair_helper(start: &str, end: &str) -> TimeEntryPair {
+        let s = DateTime::parse_from_rfc3339(start).unwrap();
+        let e = DateTime::parse_from_rfc3339(end).unwrap();
+        TimeEntryPair::new(TimeEntry::new(Direction::In, s, "Start"),
+                           TimeEntry::new(Direction::Out, e, "End"))
+    }
+
+    #[test]
+    fn split_days_midnight_test() {
+        let pair = pair_helper("2017-01-09T22:00:00-06:00",
+                               "2017-01-10T06:00:00-06:00");
+        let recs = split_days(&pair, NaiveTime::from_hms(0, 0, 0));
+        assert_eq!(recs.len(), 2);
+        assert_eq!(recs[0].date().format("%F").to_string(), "2017-01-09");
+        assert_eq!(recs[0].hours(), 2.0);
+        assert_eq!(recs[0].memo(), "Start");
+        assert_eq!(recs[1].date().format("%F").to_string(), "2017-01-10");
+        assert_eq!(recs[1].hours(), 6.0);
+        assert_eq!(recs[1].memo(), "End");
+    }
+
+    #[test]
+    fn split_days_boundary_test() {
+        let boundary = NaiveTime::from_hms(4, 0, 0);
+        let pair = pair_helper("2017-01-09T22:00:00-06:00",
+                               "2017-01-10T06:00:00-06:00");
+        let recs = split_days(&pair, boundary);
+        assert_eq!(recs.len(), 2);
+        assert_eq!(recs[0].date().format("%F").to_string(), "2017-01-09");
+        assert_eq!(recs[0].hours(), 6.0);
+        assert_eq!(recs[1].date().format("%F").to_string(), "2017-01-10");
+        assert_eq!(recs[1].hours(), 2.0);
+
+        // entirely after midnight but before the boundary
+        let pair = pair_helper("2017-01-10T01:00:00-06:00",
+                               "2017-01-10T03:00:00-06:00");
+        let recs = split_days(&pair, boundary);
+        assert_eq!(recs.len(), 1);
+        assert_eq!(recs[0].date().format("%F").to_string(), "2017-01-09");
+        assert_eq!(recs[0].hours(), 2.0);
+    }
+
+    #[test]
+    fn split_days_multi_day_test() {
+        let pair = pair_helper("2017-01-09T12:00:00-06:00",
+                               "2017-01-11T12:00:00-06:00");
+        let recs = split_days(&pair, NaiveTime::from_hms(0, 0, 0));
+        assert_eq!(recs.iter().map(|r| r.hours()).collect::<Vec<_>>(),
+                   vec![12.0, 24.0, 12.0]);
+    }
+
+    #[test]
+    fn rounded_breakdown_test() {
+        let time = DateTime::parse_from_rfc3339("2017-01-07T09:00:00-06:00")
+            .unwrap();
+        let minutes = Duration::minutes;
+        let mut start = TimeEntry::new(Direction::In, time, "");
+        start.project = String::from("acme");
+        start.tags = vec![String::from("billable")];
+        let end = TimeEntry::new(Direction::Out, time + minutes(185), "");
+        let mut rec = DateRecord::from_time_entries(&start, &end);
+        let start = TimeEntry::new(Direction::In, time + minutes(200), "");
+        let end = TimeEntry::new(Direction::Out, time + minutes(685), "");
+        rec.add(&DateRecord::from_time_entries(&start, &end));
+
+        // rounded on their own the projects would be 8.25 + 3.25 hours
+        let rounded = rec.rounded(Rounding::Up(minutes(15)));
+        assert_eq!(rounded.duration(), minutes(675));
+        fn sum<K>(durations: &BTreeMap<K, Duration>) -> Duration {
+            durations.values().fold(Duration::zero(), |acc, d| acc + *d)
+        }
+        assert_eq!(sum(rounded.projects()), rounded.duration());
+        assert_eq!(sum(rounded.labelled()), rounded.duration());
+        assert!(rounded.projects()["acme"] > minutes(185));
+        assert_eq!(rounded.tags()["billable"], rounded.projects()["acme"]);
+    }
+
+    #[test]
+    fn project_tag_test() {
+        let time = DateTime::parse_from_rfc3339("2017-01-07T09:00:00-06:00")
+            .unwrap();
+        let hour = Duration::hours(1);
+        let mut start = TimeEntry::new(Direction::In, time, "");
+        start.project = String::from("acme");
+        start.tags = vec![String::from("billable")];
+        let end = TimeEntry::new(Direction::Out, time + hour, "");
+        let mut a = DateRecord::from_time_entries(&start, &end);
+        assert!(a.has_labels());
+        assert_eq!(a.projects()["acme"], hour);
+        assert_eq!(a.tags()["billable"], hour);
+
+        let start = TimeEntry::new(Direction::In, time + hour, "");
+        let end = TimeEntry::new(Direction::Out, time + hour * 3, "");
+        let b = DateRecord::from_time_entries(&start, &end);
+        assert!(!b.has_labels());
+
+        assert!(a.combine(&b));
+        assert_eq!(a.duration(), hour * 3);
+        assert_eq!(a.projects()["acme"], hour);
+        assert_eq!(a.projects()[""], hour * 2);
+        assert_eq!(a.tags()["billable"], hour);
+        assert_eq!(a.tags().len(), 1);
     }
 
     #[test]
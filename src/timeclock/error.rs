@@ -1,10 +1,26 @@
 use csv;
 use std::error::Error;
 use std::fmt;
+use std::io;
+use timeclock::{BadRow, Direction, SCHEMA_VERSION};
 
 #[derive(Debug)]
 pub enum TimeClockError {
     Csv(csv::Error),
+    Io(io::Error),
+    NoSuchEntry,
+    AmbiguousEntry,
+    /// The direction and formatted time of the entry punched twice
+    DoublePunch(Direction, String),
+    MissingOutOfRange,
+    /// The direction of a missing entry whose usual time doesn't fall between
+    /// the formatted times of the two duplicate entries
+    NoMissingTime(Direction, String, String),
+    UnsupportedSchema(String),
+    /// A project or tags for a version 1 timesheet, which has no columns
+    /// for them
+    LegacySchema,
+    BadRow(BadRow),
 }
 
 
@@ -15,17 +31,46 @@ impl From<csv::Error> for TimeClockError {
 }
 
 
+impl From<io::Error> for TimeClockError {
+    fn from(err: io::Error) -> TimeClockError {
+        TimeClockError::Io(err)
+    }
+}
+
+
 impl Error for TimeClockError {
     fn description(&self) -> &str {
         match *self {
             TimeClockError::Csv(ref err) => err.description(),
+            TimeClockError::Io(ref err) => err.description(),
+            TimeClockError::NoSuchEntry => "No matching entry",
+            TimeClockError::AmbiguousEntry => {
+                "More than one entry matches, use the index instead"
+            }
+            TimeClockError::DoublePunch(..) => "Already clocked in or out",
+            TimeClockError::MissingOutOfRange => {
+                "The missing entry must fall between the two duplicate entries"
+            }
+            TimeClockError::NoMissingTime(..) => {
+                "The usual time for the missing entry doesn't fall between the two \
+                 duplicate entries"
+            }
+            TimeClockError::UnsupportedSchema(_) => "Unsupported schema version",
+            TimeClockError::LegacySchema => {
+                "This timesheet is schema version 1, which has no projects or tags, \
+                 run `worklog migrate` first"
+            }
+            TimeClockError::BadRow(ref row) => row.error.description(),
         }
     }
 
     fn cause(&self) -> Option<&Error> {
-        Some(match *self {
-                 TimeClockError::Csv(ref err) => err as &Error,
-             })
+        match *self {
+            TimeClockError::Csv(ref err) => Some(err as &Error),
+            TimeClockError::Io(ref err) => Some(err as &Error),
+            TimeClockError::BadRow(ref row) => Some(&row.error as &Error),
+            _ => None,
+        }
     }
 }
 
@@ -34,6 +79,25 @@ impl fmt::Display for TimeClockError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             TimeClockError::Csv(ref err) => fmt::Display::fmt(err, f),
+            TimeClockError::Io(ref err) => fmt::Display::fmt(err, f),
+            TimeClockError::DoublePunch(dir, ref time) => {
+                write!(f, "Already clocked {:#} at {}", dir, time)
+            }
+            TimeClockError::NoMissingTime(dir, ref after, ref before) => {
+                write!(f,
+                       "The missing clock {:#} goes between {} and {}, give its time with --missing",
+                       dir,
+                       after,
+                       before)
+            }
+            TimeClockError::BadRow(ref row) => write!(f, "Can't read {}", row),
+            TimeClockError::UnsupportedSchema(ref version) => {
+                write!(f,
+                       "Unsupported schema version {:?}, this worklog reads up to version {}",
+                       version,
+                       SCHEMA_VERSION)
+            }
+            _ => fmt::Display::fmt(self.description(), f),
         }
     }
 }
@@ -3,6 +3,7 @@ use std::mem;
 use timeclock::DateRecord;
 use timeclock::Direction;
 use timeclock::IntoDateRecords;
+use timeclock::PairState;
 use timeclock::TimeEntry;
 use timeclock::TimeEntryPair;
 use timeclock::now;
@@ -97,12 +98,12 @@ impl<I> Iterator for TimeEntryPairsIter<I>
                 end.dir = Direction::Out;
                 end.memo = String::from("Missing clock out.");
                 self.buf = TimeEntryOpt::from(saved);
-                Some(TimeEntryPair::new(start, end))
+                Some(TimeEntryPair::with_state(start, end, PairState::MissingOut))
             }
             (TimeEntryOpt::In(start), TimeEntryOpt::Invalid) => {
                 let end =
                     TimeEntry::new(Direction::Out, now(), "Still clocked in.");
-                Some(TimeEntryPair::new(start, end))
+                Some(TimeEntryPair::with_state(start, end, PairState::Open))
             }
             (TimeEntryOpt::In(start), TimeEntryOpt::Out(end)) => {
                 Some(TimeEntryPair::new(start, end))
@@ -113,13 +114,13 @@ impl<I> Iterator for TimeEntryPairsIter<I>
                 start.dir = Direction::In;
                 start.memo = String::from("Missing clock in.");
                 self.buf = TimeEntryOpt::from(saved);
-                Some(TimeEntryPair::new(start, end))
+                Some(TimeEntryPair::with_state(start, end, PairState::MissingIn))
             }
             (TimeEntryOpt::Out(end), TimeEntryOpt::Invalid) => {
                 let mut start = end.clone();
                 start.dir = Direction::In;
                 start.memo = String::from("Missing clock in.");
-                Some(TimeEntryPair::new(start, end))
+                Some(TimeEntryPair::with_state(start, end, PairState::MissingIn))
             }
             (TimeEntryOpt::Invalid, TimeEntryOpt::Out(_)) => unreachable!(), 
             (TimeEntryOpt::Invalid, TimeEntryOpt::In(_)) => unreachable!(), 
@@ -141,6 +142,7 @@ mod tests {
     use super::*;
     use timeclock::Direction;
     use timeclock::IntoDateRecords;
+    use timeclock::PairState;
     use timeclock::TimeEntry;
     use timeclock::TimeEntryPair;
     use timeclock::now;
@@ -166,6 +168,8 @@ mod tests {
         let y = timeentry_pairs(v.into_iter()).collect::<Vec<TimeEntryPair>>();
 
         assert_eq!(y.len(), 2);
+        assert_eq!(y[0].state(), PairState::MissingOut);
+        assert_eq!(y[1].state(), PairState::Open);
 
         for pair in y {
             let (s, e) = (pair.start(), pair.end());
@@ -187,6 +191,7 @@ mod tests {
             assert_eq!(s.dir, Direction::In);
             assert_eq!(e.dir, Direction::Out);
             assert_eq!(e.memo, "Still clocked in.");
+            assert_eq!(pair.state(), PairState::Open);
         }
     }
 
@@ -198,6 +203,7 @@ mod tests {
         let y = timeentry_pairs(v.into_iter()).collect::<Vec<TimeEntryPair>>();
 
         assert_eq!(y.len(), 1);
+        assert_eq!(y[0].state(), PairState::Complete);
 
         for pair in y {
             let (s, e) = (pair.start(), pair.end());
@@ -214,6 +220,8 @@ mod tests {
         let y = timeentry_pairs(v.into_iter()).collect::<Vec<TimeEntryPair>>();
 
         assert_eq!(y.len(), 2);
+        assert_eq!(y[0].state(), PairState::MissingIn);
+        assert_eq!(y[1].state(), PairState::MissingIn);
 
         for pair in y {
             let (s, e) = (pair.start(), pair.end());
@@ -1,36 +1,444 @@
 
+mod check;
 mod daterecord;
 mod direction;
 mod error;
 mod timeentry;
 mod traits;
 mod iterators;
+mod overtime;
+mod repair;
 
-pub use self::daterecord::DateRecord;
+pub use self::check::check;
+pub use self::daterecord::{DateRecord, split_days};
 pub use self::direction::Direction;
 pub use self::error::TimeClockError;
 pub use self::iterators::*;
-pub use self::timeentry::{TimeEntry, TimeEntryPair};
+pub use self::overtime::{OvertimeDay, OvertimeRules, OvertimeWeek, overtime};
+pub use self::repair::{Gap, duplicates, fill_gaps, gaps};
+pub use self::timeentry::{PairState, TimeEntry, TimeEntryPair};
 pub use self::traits::*;
 use chrono::*;
 use csv;
-use std::io::SeekFrom;
+use fs2::FileExt;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, SeekFrom};
 use std::io::prelude::*;
+use std::path::Path;
+use std::slice;
+use util::{self, PunchRounding, Rounding, RoundingScope};
 
+/// Number of csv fields in a `TimeEntry` row. Older files only have the
+/// first three (direction, time and memo).
+const TIME_ENTRY_FIELDS: usize = 5;
+
+/// Read `TimeEntrys` sorted by time
 pub fn read_timesheet<R: Read>(file: R)
                                -> Result<Vec<TimeEntry>, TimeClockError> {
+    let mut in_v = try!(read_entries(file));
+    in_v.sort_by_key(|k| k.time);
+    Ok(in_v)
+}
+
+/// Version of the csv layout this worklog writes. Files without a header
+/// line are version 1.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// First field of the header line, the second is the schema version
+const SCHEMA_HEADER: &'static [u8] = b"#worklog";
+
+/// The schema version from a header line
+fn schema_version(record: &csv::ByteRecord) -> Result<u32, TimeClockError> {
+    let field = String::from_utf8_lossy(record.get(1).unwrap_or(b""));
+    match field.trim().parse() {
+        Ok(version) if version > 1 && version <= SCHEMA_VERSION => Ok(version),
+        _ => Err(TimeClockError::UnsupportedSchema(field.trim().to_owned())),
+    }
+}
+
+/// How rows that can't be read are handled
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum ReadMode {
+    /// The first bad row is an error
+    Strict,
+    /// Bad rows are skipped and collected
+    Lenient,
+}
+
+/// A row of the timesheet that couldn't be read
+#[derive(Debug)]
+pub struct BadRow {
+    /// Line number, starting at 1
+    pub line: u64,
+    /// The row as it appears in the file
+    pub content: String,
+    pub error: csv::Error,
+}
+
+impl BadRow {
+    /// Why the row couldn't be read, without the position
+    pub fn reason(&self) -> String {
+        match *self.error.kind() {
+            csv::ErrorKind::Deserialize { ref err, .. } => err.to_string(),
+            _ => self.error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for BadRow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}: {:?}", self.line, self.reason(), self.content)
+    }
+}
+
+/// The contents of a timesheet file
+#[derive(Debug)]
+pub struct Timesheet {
+    pub version: u32,
+    /// In the order they appear in the file
+    pub entries: Vec<TimeEntry>,
+    /// The line each entry is on
+    pub lines: Vec<u64>,
+    /// Rows skipped in `ReadMode::Lenient`
+    pub bad_rows: Vec<BadRow>,
+}
+
+/// Read `TimeEntrys` in the order they appear in the file
+pub fn read_entries<R: Read>(file: R)
+                             -> Result<Vec<TimeEntry>, TimeClockError> {
+    let sheet = try!(read_with(file, ReadMode::Strict));
+    Ok(sheet.entries)
+}
+
+/// Read a timesheet, handling rows that can't be read according to `mode`.
+/// A header line with an unknown version is an error either way.
+pub fn read_with<R: Read>(mut file: R, mode: ReadMode) -> Result<Timesheet, TimeClockError> {
+    // keep the raw bytes around to report bad rows as they were written
+    let mut buf = Vec::new();
+    try!(file.read_to_end(&mut buf));
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(false)
+        .flexible(true)
+        .from_reader(&buf[..]);
+    let mut sheet = Timesheet {
+        version: 1,
+        entries: Vec::new(),
+        lines: Vec::new(),
+        bad_rows: Vec::new(),
+    };
+    let mut record = csv::ByteRecord::new();
+    let mut first = true;
+    // line number of the byte at `counted`
+    let (mut line, mut counted) = (1, 0);
+    while try!(rdr.read_byte_record(&mut record)) {
+        // the position can be just before the end of the last row when lines
+        // end with \r\n, so skip to the row's first byte
+        let mut start = record.position().map_or(0, |p| p.byte() as usize);
+        let end = rdr.position().byte() as usize;
+        while start < end && (buf[start] == b'\r' || buf[start] == b'\n') {
+            start += 1;
+        }
+        line += buf[counted..start].iter().filter(|&&b| b == b'\n').count() as u64;
+        counted = start;
+
+        if first && record.get(0) == Some(SCHEMA_HEADER) {
+            sheet.version = try!(schema_version(&record));
+            first = false;
+            continue;
+        }
+        first = false;
+        // pad short rows so the missing fields take their defaults
+        while record.len() < TIME_ENTRY_FIELDS {
+            record.push_field(b"");
+        }
+        match record.deserialize(None) {
+            Ok(entry) => {
+                sheet.entries.push(entry);
+                sheet.lines.push(line);
+            }
+            Err(err) => {
+                let content = String::from_utf8_lossy(&buf[start..end]);
+                let row = BadRow {
+                    line: line,
+                    content: content.trim_right_matches(|c| c == '\r' || c == '\n')
+                        .to_owned(),
+                    error: err,
+                };
+                match mode {
+                    ReadMode::Strict => return Err(TimeClockError::BadRow(row)),
+                    ReadMode::Lenient => sheet.bad_rows.push(row),
+                }
+            }
+        }
+    }
+    Ok(sheet)
+}
+
+/// Read only the schema version of a file
+pub fn read_version<R: Read>(file: R) -> Result<u32, TimeClockError> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
         .from_reader(file);
-    let mut in_v: Vec<TimeEntry> = try!(rdr.deserialize().collect());
-    in_v.sort_by_key(|k| k.time);
-    Ok(in_v)
+    let mut record = csv::ByteRecord::new();
+    if try!(rdr.read_byte_record(&mut record)) && record.get(0) == Some(SCHEMA_HEADER) {
+        schema_version(&record)
+    } else {
+        Ok(1)
+    }
+}
+
+/// Write the header line for `version`, version 1 has none
+fn write_header<W: Write>(wtr: &mut csv::Writer<W>,
+                          version: u32)
+                          -> Result<(), TimeClockError> {
+    if version > 1 {
+        try!(wtr.write_record(&[SCHEMA_HEADER, version.to_string().as_bytes()]));
+    }
+    Ok(())
+}
+
+/// Write `TimeEntrys` as csv in the layout of schema `version`
+pub fn write_timesheet<W: Write>(entries: &[TimeEntry],
+                                 version: u32,
+                                 file: W)
+                                 -> Result<(), TimeClockError> {
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_writer(file);
+    try!(write_header(&mut wtr, version));
+    for entry in entries {
+        try!(write_entry(&mut wtr, entry, version));
+    }
+    try!(wtr.flush());
+    Ok(())
+}
+
+/// Write one `TimeEntry` in the layout of schema `version`. Version 1 only
+/// has the direction, time and memo, so an entry with a project or tags
+/// can't be written without migrating first.
+fn write_entry<W: Write>(wtr: &mut csv::Writer<W>,
+                         entry: &TimeEntry,
+                         version: u32)
+                         -> Result<(), TimeClockError> {
+    if version > 1 {
+        try!(wtr.serialize(entry));
+    } else if entry.project.is_empty() && entry.tags.is_empty() {
+        try!(wtr.serialize((&entry.dir, &entry.time, &entry.memo)));
+    } else {
+        return Err(TimeClockError::LegacySchema);
+    }
+    Ok(())
+}
+
+/// How the timesheet is locked
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Lock {
+    /// For reading, any number of shared locks can be held at once
+    Shared,
+    /// For changing the timesheet, nothing else can hold a lock
+    Exclusive,
+}
+
+/// Lock the timesheet at `path`, waiting for any conflicting lock to be
+/// released. The lock is held until the returned file is dropped.
+///
+/// The lock is taken on `<path>.lock` rather than the timesheet itself, as
+/// `replace_timesheet` swaps in a new file and a lock on the old one would
+/// no longer protect anything.
+pub fn lock_timesheet(path: &Path, lock: Lock) -> Result<File, TimeClockError> {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".lock");
+    let file = try!(fs::OpenOptions::new().write(true).create(true).open(name));
+    match lock {
+        Lock::Shared => try!(file.lock_shared()),
+        Lock::Exclusive => try!(file.lock_exclusive()),
+    }
+    Ok(file)
+}
+
+/// Replace the timesheet at `path` with `entries`, keeping its schema
+/// version. A new timesheet gets the current version.
+pub fn replace_timesheet(path: &Path,
+                         entries: &[TimeEntry])
+                         -> Result<(), TimeClockError> {
+    let version = match File::open(path) {
+        Ok(file) => try!(read_version(file)),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => SCHEMA_VERSION,
+        Err(e) => return Err(TimeClockError::from(e)),
+    };
+    rewrite_timesheet(path, entries, version)
+}
+
+/// Replace the timesheet at `path` with `entries` written as schema
+/// `version`, see `replace_file`.
+pub fn rewrite_timesheet(path: &Path,
+                         entries: &[TimeEntry],
+                         version: u32)
+                         -> Result<(), TimeClockError> {
+    replace_file(path, |file| write_timesheet(entries, version, file))
+}
+
+/// Replace the file at `path` with whatever `write` writes.
+///
+/// It's written to a temporary file alongside the original which is then
+/// renamed over it, so the file is never left half written.
+pub fn replace_file<F>(path: &Path, write: F) -> Result<(), TimeClockError>
+    where F: FnOnce(&mut File) -> Result<(), TimeClockError>
+{
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(path.file_name().unwrap_or_else(|| path.as_os_str()));
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let res = File::create(&tmp_path)
+        .map_err(TimeClockError::from)
+        .and_then(|mut file| {
+            try!(write(&mut file));
+            try!(file.sync_all());
+            Ok(())
+        })
+        .and_then(|_| fs::rename(&tmp_path, path).map_err(TimeClockError::from));
+
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res
+}
+
+/// Find the index of the entry recorded in the same minute as `time`
+pub fn find_entry(entries: &[TimeEntry],
+                  time: DateTime<FixedOffset>)
+                  -> Result<usize, TimeClockError> {
+    let minute = time.timestamp() / 60;
+    let mut found = entries.iter()
+        .enumerate()
+        .filter(|&(_, e)| e.time.timestamp() / 60 == minute)
+        .map(|(i, _)| i);
+    match (found.next(), found.next()) {
+        (Some(i), None) => Ok(i),
+        (Some(_), Some(_)) => Err(TimeClockError::AmbiguousEntry),
+        (None, _) => Err(TimeClockError::NoSuchEntry),
+    }
+}
+
+/// The entry before `entry` if it's in the same direction, ie. `entry`
+/// would be a second clock in or clock out.
+pub fn double_punch<'a>(entries: &'a [TimeEntry],
+                        entry: &TimeEntry)
+                        -> Option<&'a TimeEntry> {
+    entries.iter()
+        .rev()
+        .find(|e| e.time <= entry.time)
+        .and_then(|prev| if prev.dir == entry.dir {
+                      Some(prev)
+                  } else {
+                      None
+                  })
+}
+
+/// Create the opposite entry that belongs between two entries in the same
+/// direction. `time` must fall between them.
+pub fn missing_entry(prev: &TimeEntry,
+                     next: &TimeEntry,
+                     time: DateTime<FixedOffset>)
+                     -> Result<TimeEntry, TimeClockError> {
+    if time < prev.time || time > next.time {
+        return Err(TimeClockError::MissingOutOfRange);
+    }
+    let dir = match prev.dir {
+        Direction::In => Direction::Out,
+        Direction::Out => Direction::In,
+    };
+    let mut entry = inserted_entry(dir, time);
+    // the missing entry carries on the project of the interval it closes
+    if dir == Direction::Out {
+        entry.project = prev.project.clone();
+    }
+    Ok(entry)
+}
+
+/// An entry inserted where one was missing
+fn inserted_entry(dir: Direction, time: DateTime<FixedOffset>) -> TimeEntry {
+    let memo = match dir {
+        Direction::In => "Inserted missing clock in.",
+        Direction::Out => "Inserted missing clock out.",
+    };
+    TimeEntry::new(dir, time, memo)
+}
+
+/// Round the time of each In and Out entry, keeping the punched time in
+/// `raw_time`. Entries stay in order, a time that would round to before
+/// the previous entry is moved up to it.
+pub fn round_punches(entries: Vec<TimeEntry>,
+                     round_in: Option<PunchRounding>,
+                     round_out: Option<PunchRounding>)
+                     -> Vec<TimeEntry> {
+    let mut prev: Option<DateTime<FixedOffset>> = None;
+    entries.into_iter()
+        .map(|mut entry| {
+            let rounding = match entry.dir {
+                Direction::In => round_in,
+                Direction::Out => round_out,
+            };
+            if let Some(rounding) = rounding {
+                let mut time = rounding.round(entry.time);
+                if let Some(prev) = prev {
+                    time = time.max(prev);
+                }
+                if time != entry.time {
+                    entry.raw_time = Some(entry.time);
+                    entry.time = time;
+                }
+            }
+            prev = Some(entry.time);
+            entry
+        })
+        .collect()
+}
+
+/// How time that crosses from one day into the next is credited
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum DayAttribution {
+    /// Credit the whole interval to the day it started on
+    Start,
+    /// Split the interval where the day changes, at this time of day
+    Split(NaiveTime),
 }
 
+#[allow(dead_code)]
 /// Reduce pairs of `TimeEntrys` into `DateRecords`
 pub fn collect_date_records(records: Vec<TimeEntry>) -> Vec<DateRecord> {
+    collect_date_records_by(records, DayAttribution::Start)
+}
+
+/// Reduce pairs of `TimeEntrys` into `DateRecords`, crediting intervals
+/// that cross the day boundary according to `attribution`
+pub fn collect_date_records_by(records: Vec<TimeEntry>,
+                               attribution: DayAttribution)
+                               -> Vec<DateRecord> {
+    collect_date_records_rounded(records, attribution, Rounding::None)
+}
+
+/// Like `collect_date_records_by`, but each pair is rounded before the
+/// pairs for a day are added up
+pub fn collect_date_records_rounded(records: Vec<TimeEntry>,
+                                    attribution: DayAttribution,
+                                    rounding: Rounding)
+                                    -> Vec<DateRecord> {
+    let pairs = timeentry_pairs(records.into_iter());
+    let daterecords: Box<Iterator<Item = DateRecord>> = match attribution {
+        DayAttribution::Start => Box::new(pairs.daterecords()),
+        DayAttribution::Split(boundary) => {
+            Box::new(pairs.flat_map(move |p| split_days(&p, boundary)))
+        }
+    };
+
     let mut res: Vec<DateRecord> = Vec::new();
-    for rec in timeentry_pairs(records.into_iter()).daterecords() {
+    for rec in daterecords.map(|r| r.rounded(rounding)) {
         match res.pop() {
             Some(mut r) => {
                 if r.combine(&rec) {
@@ -47,18 +455,139 @@ pub fn collect_date_records(records: Vec<TimeEntry>) -> Vec<DateRecord> {
 }
 
 
-/// Marks the time.
-pub fn mark_time<W: Write + Seek>(dir: Direction,
-                                  time: DateTime<FixedOffset>,
-                                  memo: &str,
-                                  file: &mut W) {
-    let record = TimeEntry::new(dir, time, memo);
-    // seek in case we write without reading first
-    let _ = file.seek(SeekFrom::End(0));
+/// Group daily records by `scope` and round each group. Pair rounding has to
+/// happen while collecting the records, so those are only copied. Summing
+/// the groups gives the rounded total.
+pub fn round_records(records: &[DateRecord],
+                     rounding: Rounding,
+                     scope: RoundingScope,
+                     week_start: Weekday)
+                     -> Vec<DateRecord> {
+    let groups = scope_records(records, scope, week_start);
+    if scope == RoundingScope::Pair {
+        groups
+    } else {
+        groups.iter().map(|g| g.rounded(rounding)).collect()
+    }
+}
+
+
+/// Add up daily records into one per day, week or period, the groups
+/// `round_records` rounds. Each is dated its first day.
+pub fn scope_records(records: &[DateRecord],
+                     scope: RoundingScope,
+                     week_start: Weekday)
+                     -> Vec<DateRecord> {
+    let mut groups: Vec<DateRecord> = Vec::new();
+    for rec in records {
+        let key = match scope {
+            RoundingScope::Pair | RoundingScope::Day => rec.date(),
+            RoundingScope::Week => util::week_start(rec.date(), week_start),
+            RoundingScope::Period => records[0].date(),
+        };
+        let same = groups.last().map_or(false, |g| g.date() == key);
+        if same {
+            groups.last_mut().unwrap().add(rec);
+        } else {
+            let mut group = rec.clone();
+            group.set_date(key);
+            groups.push(group);
+        }
+    }
+    groups
+}
+
+
+/// Everything in `records` added into one record, dated the first day
+pub fn sum_records(records: &[DateRecord]) -> Option<DateRecord> {
+    let mut iter = records.iter();
+    iter.next().map(|first| {
+                        let mut total = first.clone();
+                        for rec in iter {
+                            total.add(rec);
+                        }
+                        total
+                    })
+}
+
+
+/// Days falling in the same week, month, year or pay period
+#[derive(Clone,Debug)]
+pub struct RecordGroup {
+    pub start: Date<FixedOffset>,
+    pub end: Date<FixedOffset>,
+    pub records: Vec<DateRecord>,
+}
+
+impl RecordGroup {
+    /// The subtotal for the group
+    pub fn total(&self) -> Option<DateRecord> {
+        sum_records(&self.records)
+    }
+}
+
+
+/// Split date ordered `records` into groups. `period` gives the first and
+/// last days of the group a date belongs to.
+pub fn group_records<F>(records: &[DateRecord], period: F) -> Vec<RecordGroup>
+    where F: Fn(Date<FixedOffset>) -> (Date<FixedOffset>, Date<FixedOffset>)
+{
+    let mut groups: Vec<RecordGroup> = Vec::new();
+    for rec in records {
+        let (start, end) = period(rec.date());
+        let same = groups.last().map_or(false, |g| g.start == start);
+        if same {
+            groups.last_mut().unwrap().records.push(rec.clone());
+        } else {
+            groups.push(RecordGroup {
+                            start: start,
+                            end: end,
+                            records: vec![rec.clone()],
+                        });
+        }
+    }
+    groups
+}
+
+
+/// Marks the time, appending `record` to the end of `file`, see
+/// `mark_times`.
+pub fn mark_time<F: Read + Write + Seek>(record: &TimeEntry,
+                                         file: &mut F)
+                                         -> Result<(), TimeClockError> {
+    mark_times(slice::from_ref(record), file)
+}
+
+/// Appends `records` to the end of `file` in the layout of its schema
+/// version. An empty file gets the header line for the current schema
+/// version first. Nothing is written if any record doesn't fit the layout.
+pub fn mark_times<F: Read + Write + Seek>(records: &[TimeEntry],
+                                          file: &mut F)
+                                          -> Result<(), TimeClockError> {
+    let end = try!(file.seek(SeekFrom::End(0)));
+    let version = if end == 0 {
+        SCHEMA_VERSION
+    } else {
+        try!(file.seek(SeekFrom::Start(0)));
+        let version = try!(read_version(&mut *file));
+        try!(file.seek(SeekFrom::End(0)));
+        version
+    };
+    if version == 1 && records.iter().any(|r| !r.project.is_empty() || !r.tags.is_empty()) {
+        return Err(TimeClockError::LegacySchema);
+    }
     let mut wtr = csv::WriterBuilder::new()
         .has_headers(false)
+        .flexible(true)
         .from_writer(file);
-    let _ = wtr.serialize(record);
+    if end == 0 {
+        try!(write_header(&mut wtr, SCHEMA_VERSION));
+    }
+    for record in records {
+        try!(write_entry(&mut wtr, record, version));
+    }
+    try!(wtr.flush());
+    Ok(())
 }
 
 /// Get the current date and time as a `DateTime`<`FixedOffset`>
@@ -71,7 +600,9 @@ pub fn now() -> DateTime<FixedOffset> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
+    use std::env;
+    use std::io::{self, Cursor};
+    use std::process;
 
     #[test]
     fn read_timesheet_test() {
@@ -93,6 +624,268 @@ mod tests {
     }
 
 
+    #[test]
+    fn read_timesheet_project_test() {
+        // legacy three column rows mixed with project and tag rows
+        let s = "In,2016-12-18T13:01:50-0600,\n\
+                 Out,2016-12-18T16:53:33-0600,Lunch,acme,billable urgent\n\
+                 In,2016-12-19T20:54:53-0600,,acme\n";
+        let buff = Cursor::new(s.as_bytes());
+        let records = read_timesheet(buff).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].project, "");
+        assert!(records[0].tags.is_empty());
+        assert_eq!(records[1].memo, "Lunch");
+        assert_eq!(records[1].project, "acme");
+        assert_eq!(records[1].tags, vec!["billable", "urgent"]);
+        assert_eq!(records[2].project, "acme");
+        assert!(records[2].tags.is_empty());
+    }
+
+
+    #[test]
+    fn write_timesheet_test() {
+        let s = "In,2016-12-18T13:01:50-06:00,,acme,billable\n\
+                 Out,2016-12-18T16:53:33-06:00,Lunch,,\n";
+        let entries = read_timesheet(Cursor::new(s.as_bytes())).unwrap();
+        let mut buff: Vec<u8> = Vec::new();
+        write_timesheet(&entries, 2, &mut buff).unwrap();
+        assert_eq!(String::from_utf8(buff).unwrap(), format!("#worklog,2\n{}", s));
+
+        // version 1 has no columns for the project and tags
+        match write_timesheet(&entries, 1, Vec::new()) {
+            Err(TimeClockError::LegacySchema) => {}
+            e => panic!("{:?}", e),
+        }
+        let mut buff: Vec<u8> = Vec::new();
+        write_timesheet(&entries[1..], 1, &mut buff).unwrap();
+        assert_eq!(String::from_utf8(buff).unwrap(),
+                   "Out,2016-12-18T16:53:33-06:00,Lunch\n");
+    }
+
+
+    #[test]
+    fn read_version_test() {
+        let s = "In,2016-12-18T13:01:50-06:00,\n";
+        let legacy = read_with(Cursor::new(s.as_bytes()), ReadMode::Strict).unwrap();
+        assert_eq!(legacy.version, 1);
+        assert_eq!(read_version(Cursor::new(s.as_bytes())).unwrap(), 1);
+        assert_eq!(read_version(Cursor::new(&b""[..])).unwrap(), 1);
+
+        let v2 = format!("#worklog,2\n{}", s);
+        let sheet = read_with(Cursor::new(v2.as_bytes()), ReadMode::Strict).unwrap();
+        assert_eq!(sheet.version, 2);
+        assert_eq!(sheet.entries, legacy.entries);
+        assert_eq!(read_version(Cursor::new(v2.as_bytes())).unwrap(), 2);
+
+        for header in &["#worklog,3", "#worklog,one", "#worklog"] {
+            let s = format!("{}\n{}", header, s);
+            match read_timesheet(Cursor::new(s.as_bytes())) {
+                Err(TimeClockError::UnsupportedSchema(_)) => {}
+                e => panic!("{:?}", e),
+            }
+        }
+    }
+
+
+    #[test]
+    fn find_entry_test() {
+        let s = "In,2016-12-18T13:01:50-06:00,\n\
+                 Out,2016-12-18T16:53:33-06:00,\n\
+                 In,2016-12-18T16:53:59-06:00,\n";
+        let entries = read_timesheet(Cursor::new(s.as_bytes())).unwrap();
+        let time = DateTime::parse_from_rfc3339("2016-12-18T13:01:00-06:00")
+            .unwrap();
+        assert_eq!(find_entry(&entries, time).unwrap(), 0);
+        // same instant in another timezone
+        let time = DateTime::parse_from_rfc3339("2016-12-18T14:01:00-05:00")
+            .unwrap();
+        assert_eq!(find_entry(&entries, time).unwrap(), 0);
+        let time = DateTime::parse_from_rfc3339("2016-12-18T16:53:00-06:00")
+            .unwrap();
+        match find_entry(&entries, time) {
+            Err(TimeClockError::AmbiguousEntry) => {}
+            e => panic!("{:?}", e),
+        }
+        let time = DateTime::parse_from_rfc3339("2016-12-18T09:00:00-06:00")
+            .unwrap();
+        match find_entry(&entries, time) {
+            Err(TimeClockError::NoSuchEntry) => {}
+            e => panic!("{:?}", e),
+        }
+    }
+
+
+    #[test]
+    fn replace_timesheet_test() {
+        let path = env::temp_dir()
+            .join(format!("worklog-replace-{}.csv", process::id()));
+        let time = DateTime::parse_from_rfc3339("2017-01-18T12:50:13-06:00")
+            .unwrap();
+        let entries = vec![TimeEntry::new(Direction::In, time, "Test")];
+        fs::write(&path, "garbage").unwrap();
+        replace_timesheet(&path, &entries).unwrap();
+        let records = read_timesheet(File::open(&path).unwrap()).unwrap();
+        assert_eq!(records, entries);
+        // keeps the schema version
+        assert_eq!(read_version(File::open(&path).unwrap()).unwrap(), 1);
+        rewrite_timesheet(&path, &entries, SCHEMA_VERSION).unwrap();
+        replace_timesheet(&path, &entries).unwrap();
+        assert_eq!(read_version(File::open(&path).unwrap()).unwrap(), SCHEMA_VERSION);
+        fs::remove_file(&path).unwrap();
+        // a new timesheet gets the current version
+        replace_timesheet(&path, &entries).unwrap();
+        let sheet = read_with(File::open(&path).unwrap(), ReadMode::Strict).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(sheet.version, SCHEMA_VERSION);
+        assert_eq!(sheet.entries, entries);
+    }
+
+    #[test]
+    fn legacy_round_trip_test() {
+        let path = env::temp_dir()
+            .join(format!("worklog-legacy-{}.csv", process::id()));
+        let time = DateTime::parse_from_rfc3339("2017-01-18T12:50:13-06:00")
+            .unwrap();
+        let mut entries = vec![TimeEntry::new(Direction::In, time, "Test")];
+        fs::write(&path, "In,2017-01-18T08:00:00-06:00,\n").unwrap();
+        replace_timesheet(&path, &entries).unwrap();
+        let out = TimeEntry::new(Direction::Out, time + Duration::hours(1), "");
+        mark_time(&out, &mut fs::OpenOptions::new().read(true).write(true).open(&path).unwrap())
+            .unwrap();
+        let s = fs::read_to_string(&path).unwrap();
+        assert_eq!(s,
+                   "In,2017-01-18T12:50:13-06:00,Test\n\
+                   Out,2017-01-18T13:50:13-06:00,\n");
+        assert!(s.lines().all(|l| l.split(',').count() == 3));
+        entries.push(out);
+        assert_eq!(read_timesheet(File::open(&path).unwrap()).unwrap(), entries);
+
+        // projects and tags need migrating first
+        let mut tagged = entries[1].clone();
+        tagged.project = String::from("acme");
+        match replace_timesheet(&path, &[tagged.clone()]) {
+            Err(TimeClockError::LegacySchema) => {}
+            e => panic!("{:?}", e),
+        }
+        let mut file = fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        match mark_times(&[entries[0].clone(), tagged], &mut file) {
+            Err(TimeClockError::LegacySchema) => {}
+            e => panic!("{:?}", e),
+        }
+        let unchanged = read_timesheet(File::open(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(unchanged, entries);
+    }
+
+
+    #[test]
+    fn lock_timesheet_test() {
+        let path = env::temp_dir()
+            .join(format!("worklog-lock-{}.csv", process::id()));
+        let lock_path = env::temp_dir()
+            .join(format!("worklog-lock-{}.csv.lock", process::id()));
+        let try_lock = |exclusive| {
+            let file = File::open(&lock_path).unwrap();
+            if exclusive {
+                file.try_lock_exclusive().is_ok()
+            } else {
+                file.try_lock_shared().is_ok()
+            }
+        };
+        {
+            let _lock = lock_timesheet(&path, Lock::Shared).unwrap();
+            assert!(try_lock(false));
+            assert!(!try_lock(true));
+        }
+        {
+            let _lock = lock_timesheet(&path, Lock::Exclusive).unwrap();
+            assert!(!try_lock(false));
+        }
+        // released when dropped
+        assert!(try_lock(true));
+        fs::remove_file(&lock_path).unwrap();
+    }
+
+    #[test]
+    fn mark_time_error_test() {
+        // a writer that refuses everything
+        struct Full;
+        impl Write for Full {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        impl Read for Full {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Ok(0)
+            }
+        }
+        impl Seek for Full {
+            fn seek(&mut self, _: SeekFrom) -> io::Result<u64> {
+                Ok(0)
+            }
+        }
+        let time = DateTime::parse_from_rfc3339("2017-01-18T12:50:13-06:00")
+            .unwrap();
+        let te = TimeEntry::new(Direction::In, time, "Test");
+        match mark_time(&te, &mut Full) {
+            Err(TimeClockError::Io(_)) => {}
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn double_punch_test() {
+        let s = "In,2016-12-18T09:00:00-06:00,\n\
+                 Out,2016-12-18T17:00:00-06:00,\n";
+        let entries = read_timesheet(Cursor::new(s.as_bytes())).unwrap();
+        let time = DateTime::parse_from_rfc3339("2016-12-19T09:00:00-06:00")
+            .unwrap();
+        let entry = TimeEntry::new(Direction::In, time, "");
+        assert_eq!(double_punch(&entries, &entry), None);
+        let entry = TimeEntry::new(Direction::Out, time, "");
+        assert_eq!(double_punch(&entries, &entry), Some(&entries[1]));
+        // backdated between the two
+        let time = DateTime::parse_from_rfc3339("2016-12-18T12:00:00-06:00")
+            .unwrap();
+        let entry = TimeEntry::new(Direction::In, time, "");
+        assert_eq!(double_punch(&entries, &entry), Some(&entries[0]));
+        let entry = TimeEntry::new(Direction::Out, time, "");
+        assert_eq!(double_punch(&entries, &entry), None);
+        // before everything
+        let time = DateTime::parse_from_rfc3339("2016-12-17T12:00:00-06:00")
+            .unwrap();
+        let entry = TimeEntry::new(Direction::Out, time, "");
+        assert_eq!(double_punch(&entries, &entry), None);
+    }
+
+
+    #[test]
+    fn missing_entry_test() {
+        let time = DateTime::parse_from_rfc3339("2016-12-18T09:00:00-06:00")
+            .unwrap();
+        let mut prev = TimeEntry::new(Direction::In, time, "");
+        prev.project = String::from("acme");
+        let next = TimeEntry::new(Direction::In,
+                                  time + Duration::days(1),
+                                  "");
+        let missing = missing_entry(&prev, &next, time + Duration::hours(8))
+            .unwrap();
+        assert_eq!(missing.dir, Direction::Out);
+        assert_eq!(missing.time, time + Duration::hours(8));
+        assert_eq!(missing.project, "acme");
+
+        match missing_entry(&prev, &next, time - Duration::hours(1)) {
+            Err(TimeClockError::MissingOutOfRange) => {}
+            e => panic!("{:?}", e),
+        }
+    }
+
+
     #[test]
     fn read_timesheet_haggis_test() {
         // test for sorting and general function
@@ -108,6 +901,32 @@ mod tests {
     }
 
 
+    #[test]
+    fn read_with_lenient_test() {
+        let s = "#worklog,2\r\n\
+                 In,2016-12-18T13:01:50-06:00,\r\n\
+                 In,yesterday,\"oops, typo\"\r\n\
+                 Out,2016-12-18T16:53:33-06:00,\r\n\
+                 Sideways,2016-12-18T17:00:00-06:00,";
+        let sheet = read_with(Cursor::new(s.as_bytes()), ReadMode::Lenient).unwrap();
+        assert_eq!(sheet.entries.len(), 2);
+        assert_eq!(sheet.lines, vec![2, 4]);
+        assert_eq!(sheet.bad_rows.len(), 2);
+        assert_eq!(sheet.bad_rows[0].line, 3);
+        assert_eq!(sheet.bad_rows[0].content, "In,yesterday,\"oops, typo\"");
+        assert_eq!(sheet.bad_rows[1].line, 5);
+        assert_eq!(sheet.bad_rows[1].content, "Sideways,2016-12-18T17:00:00-06:00,");
+        assert!(sheet.bad_rows[1].reason().contains("Sideways"),
+                "{}",
+                sheet.bad_rows[1].reason());
+
+        match read_with(Cursor::new(s.as_bytes()), ReadMode::Strict) {
+            Err(TimeClockError::BadRow(ref row)) => assert_eq!(row.line, 3),
+            e => panic!("{:?}", e),
+        }
+    }
+
+
     #[test]
     fn pair_time_entries_test() {
         let s = "Out,2016-12-18T13:01:50-0600,\n\
@@ -182,14 +1001,137 @@ mod tests {
         assert!(records[0].seconds() == 3600.0);
     }
 
+    #[test]
+    fn collect_date_records_split_test() {
+        let s = "In,2016-12-18T22:00:00-06:00,\n\
+                 Out,2016-12-19T06:00:00-06:00,\n\
+                 In,2016-12-19T22:00:00-06:00,\n\
+                 Out,2016-12-20T06:00:00-06:00,";
+        let entries = read_timesheet(Cursor::new(s.as_bytes())).unwrap();
+
+        let records = collect_date_records_by(entries.clone(),
+                                              DayAttribution::Start);
+        assert_eq!(records.iter().map(|r| r.hours()).collect::<Vec<_>>(),
+                   vec![8.0, 8.0]);
+
+        let midnight = NaiveTime::from_hms(0, 0, 0);
+        let records = collect_date_records_by(entries,
+                                              DayAttribution::Split(midnight));
+        // the morning of the 19th and evening of the 19th are combined
+        assert_eq!(records.iter().map(|r| r.hours()).collect::<Vec<_>>(),
+                   vec![2.0, 8.0, 6.0]);
+    }
+
+    #[test]
+    fn round_punches_test() {
+        let s = "In,2017-01-02T08:07:00-06:00,\n\
+                 Out,2017-01-02T16:53:00-06:00,\n\
+                 In,2017-01-02T17:01:00-06:00,\n\
+                 Out,2017-01-02T17:05:00-06:00,";
+        let entries = read_timesheet(Cursor::new(s.as_bytes())).unwrap();
+        let quarter = Duration::minutes(15);
+        let round_in = PunchRounding::new(Rounding::Up(quarter), None);
+        let round_out = PunchRounding::new(Rounding::Down(quarter), None);
+        let rounded = round_punches(entries.clone(), Some(round_in), Some(round_out));
+
+        let times: Vec<String> = rounded.iter().map(|e| e.time.format("%R").to_string()).collect();
+        // the last Out can't round down to before its In
+        assert_eq!(times, vec!["08:15", "16:45", "17:15", "17:15"]);
+        assert_eq!(rounded[0].raw_time, Some(entries[0].time));
+        assert_eq!(rounded[3].raw_time, Some(entries[3].time));
+
+        // only Out times are rounded, and unchanged times keep no raw time
+        let rounded = round_punches(entries.clone(), None, Some(round_out));
+        assert_eq!(rounded[0], entries[0]);
+        assert_eq!(rounded[1].time.format("%R").to_string(), "16:45");
+
+        // the raw time isn't written out
+        let mut buff = Cursor::new(Vec::new());
+        write_timesheet(&rounded, 1, &mut buff).unwrap();
+        let written = String::from_utf8(buff.into_inner()).unwrap();
+        assert!(written.contains("16:45:00"));
+        assert!(!written.contains("16:53:00"));
+    }
+
+    #[test]
+    fn round_records_test() {
+        // 2017-01-02 is a Monday
+        let s = "In,2017-01-02T09:00:00-06:00,\n\
+                 Out,2017-01-02T09:05:00-06:00,\n\
+                 In,2017-01-02T10:00:00-06:00,\n\
+                 Out,2017-01-02T10:05:00-06:00,\n\
+                 In,2017-01-03T09:00:00-06:00,\n\
+                 Out,2017-01-03T09:52:00-06:00,\n\
+                 In,2017-01-09T09:00:00-06:00,\n\
+                 Out,2017-01-09T09:10:00-06:00,";
+        let entries = read_timesheet(Cursor::new(s.as_bytes())).unwrap();
+        let rounding = Rounding::Up(Duration::minutes(15));
+        let records = collect_date_records_by(entries.clone(), DayAttribution::Start);
+        let total = |recs: &[DateRecord]| sum_records(recs).unwrap().duration().num_minutes();
+        let round = |scope| {
+            let groups = round_records(&records, rounding, scope, Weekday::Mon);
+            (groups.len(), total(&groups))
+        };
+
+        assert_eq!(total(&records), 72);
+        assert_eq!(round(RoundingScope::Day), (3, 90));
+        assert_eq!(round(RoundingScope::Week), (2, 90));
+        assert_eq!(round(RoundingScope::Period), (1, 75));
+
+        let pairs = collect_date_records_rounded(entries, DayAttribution::Start, rounding);
+        assert_eq!(pairs[0].duration(), Duration::minutes(30));
+        let groups = round_records(&pairs, rounding, RoundingScope::Pair, Weekday::Mon);
+        assert_eq!(total(&groups), 105);
+    }
+
+    #[test]
+    fn group_records_test() {
+        let s = "In,2017-01-30T09:00:00-06:00,\n\
+                 Out,2017-01-30T10:00:00-06:00,\n\
+                 In,2017-01-31T09:00:00-06:00,\n\
+                 Out,2017-01-31T11:00:00-06:00,\n\
+                 In,2017-02-01T09:00:00-06:00,\n\
+                 Out,2017-02-01T12:00:00-06:00,";
+        let entries = read_timesheet(Cursor::new(s.as_bytes())).unwrap();
+        let records = collect_date_records(entries);
+        let by = |grouping| {
+            group_records(&records, |d| {
+                util::period(d, grouping, Weekday::Mon, util::PayPeriod::Weekly)
+            })
+        };
+
+        let weeks = by(util::Grouping::Week);
+        assert_eq!(weeks.len(), 1);
+        assert_eq!(weeks[0].start, records[0].date());
+        assert_eq!(weeks[0].total().unwrap().duration(), Duration::hours(6));
+
+        let months = by(util::Grouping::Month);
+        assert_eq!(months.len(), 2);
+        assert_eq!(months[0].records.len(), 2);
+        assert_eq!(months[0].end, records[1].date());
+        assert_eq!(months[0].total().unwrap().duration(), Duration::hours(3));
+        assert_eq!(months[1].total().unwrap().duration(), Duration::hours(3));
+    }
+
     #[test]
     fn mark_time_test() {
         let mut buff: Cursor<Vec<u8>> = Cursor::new(Vec::new());
         let time = DateTime::parse_from_rfc3339("2017-01-18T12:50:13-06:00")
             .unwrap();
-        mark_time(Direction::In, time, "Test", &mut buff);
+        let mut te = TimeEntry::new(Direction::In, time, "Test");
+        mark_time(&te, &mut buff).unwrap();
+        te.project = String::from("acme");
+        te.tags = vec![String::from("billable"), String::from("urgent")];
+        mark_time(&te, &mut buff).unwrap();
         let v = buff.into_inner();
         let s = String::from_utf8(v).unwrap();
-        assert_eq!(s, "In,2017-01-18T12:50:13-06:00,Test\n");
+        assert_eq!(s,
+                   "#worklog,2\n\
+                   In,2017-01-18T12:50:13-06:00,Test,,\n\
+                   In,2017-01-18T12:50:13-06:00,Test,acme,billable urgent\n");
+
+        // round trip
+        let records = read_timesheet(Cursor::new(s.as_bytes())).unwrap();
+        assert_eq!(records[1], te);
     }
 }
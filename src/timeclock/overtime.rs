@@ -0,0 +1,235 @@
+// Overtime rules.
+//
+// Days are split into regular, overtime and double time hours. Daily limits
+// are applied first, then whatever regular time goes over the weekly limit
+// becomes overtime. With the seventh day rule, working every day of a week
+// makes the whole seventh day overtime, and anything past the daily limit
+// on it double time.
+
+use chrono::prelude::*;
+use chrono::Duration;
+use timeclock::{DateRecord, group_records};
+use util::{self, Grouping, PayPeriod};
+
+/// When time counts as overtime or double time
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct OvertimeRules {
+    /// Time worked in a day before overtime
+    pub daily: Option<Duration>,
+    /// Time worked in a day before double time
+    pub daily_double: Option<Duration>,
+    /// Regular time worked in a week before overtime
+    pub weekly: Option<Duration>,
+    /// Whether the seventh consecutive day in a week is overtime
+    pub seventh_day: bool,
+}
+
+impl OvertimeRules {
+    /// 40 hours a week, with no daily limits
+    pub fn new() -> OvertimeRules {
+        OvertimeRules {
+            daily: None,
+            daily_double: None,
+            weekly: Some(Duration::hours(40)),
+            seventh_day: false,
+        }
+    }
+}
+
+/// A day's time split by rate
+#[derive(Clone,Debug,PartialEq)]
+pub struct OvertimeDay {
+    pub date: Date<FixedOffset>,
+    pub regular: Duration,
+    pub overtime: Duration,
+    pub double: Duration,
+}
+
+impl OvertimeDay {
+    /// All the time worked
+    pub fn total(&self) -> Duration {
+        self.regular + self.overtime + self.double
+    }
+
+    /// Add up `days`, dated `date`
+    pub fn sum<'a, I>(date: Date<FixedOffset>, days: I) -> OvertimeDay
+        where I: Iterator<Item = &'a OvertimeDay>
+    {
+        let zero = Duration::zero();
+        let init = OvertimeDay {
+            date: date,
+            regular: zero,
+            overtime: zero,
+            double: zero,
+        };
+        days.fold(init, |acc, d| {
+            OvertimeDay {
+                date: acc.date,
+                regular: acc.regular + d.regular,
+                overtime: acc.overtime + d.overtime,
+                double: acc.double + d.double,
+            }
+        })
+    }
+}
+
+/// A week's days split by rate
+#[derive(Clone,Debug,PartialEq)]
+pub struct OvertimeWeek {
+    pub start: Date<FixedOffset>,
+    pub days: Vec<OvertimeDay>,
+}
+
+impl OvertimeWeek {
+    /// The sum of the days
+    pub fn total(&self) -> OvertimeDay {
+        OvertimeDay::sum(self.start, self.days.iter())
+    }
+}
+
+/// The part of `duration` over `limit`, zero if there's no limit
+fn over(duration: Duration, limit: Option<Duration>) -> Duration {
+    match limit {
+        Some(limit) if duration > limit => duration - limit,
+        _ => Duration::zero(),
+    }
+}
+
+/// Split one day's time, on its own
+fn split_day(date: Date<FixedOffset>,
+             worked: Duration,
+             rules: &OvertimeRules,
+             seventh: bool)
+             -> OvertimeDay {
+    let zero = Duration::zero();
+    if seventh {
+        let double = over(worked, rules.daily.or(Some(Duration::hours(8))));
+        return OvertimeDay {
+                   date: date,
+                   regular: zero,
+                   overtime: worked - double,
+                   double: double,
+               };
+    }
+    let double = over(worked, rules.daily_double);
+    let overtime = over(worked - double, rules.daily);
+    OvertimeDay {
+        date: date,
+        regular: worked - double - overtime,
+        overtime: overtime,
+        double: double,
+    }
+}
+
+/// Split date ordered daily `records` into regular, overtime and double
+/// time, by weeks starting on `week_start`
+pub fn overtime(records: &[DateRecord],
+                rules: &OvertimeRules,
+                week_start: Weekday)
+                -> Vec<OvertimeWeek> {
+    let weeks = group_records(records, |date| {
+        util::period(date, Grouping::Week, week_start, PayPeriod::Weekly)
+    });
+    weeks.iter()
+        .map(|week| {
+            let mut regular = Duration::zero();
+            let mut days = Vec::new();
+            let mut consecutive = 0;
+            let mut last: Option<Date<FixedOffset>> = None;
+            for rec in &week.records {
+                let worked = rec.duration();
+                if worked <= Duration::zero() {
+                    continue;
+                }
+                let follows = last.map_or(false, |d| d + Duration::days(1) == rec.date());
+                consecutive = if follows { consecutive + 1 } else { 1 };
+                last = Some(rec.date());
+
+                let seventh = rules.seventh_day && consecutive == 7;
+                let mut day = split_day(rec.date(), worked, rules, seventh);
+                let weekly = over(regular + day.regular, rules.weekly).min(day.regular);
+                day.regular = day.regular - weekly;
+                day.overtime = day.overtime + weekly;
+                regular = regular + day.regular;
+                days.push(day);
+            }
+            OvertimeWeek {
+                start: week.start,
+                days: days,
+            }
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2017-01-02 is a Monday
+    fn week(hours: &[i64]) -> Vec<DateRecord> {
+        let monday = FixedOffset::west(6 * 3600).ymd(2017, 1, 2);
+        hours.iter()
+            .enumerate()
+            .map(|(i, h)| {
+                     DateRecord::from_parts(monday + Duration::days(i as i64),
+                                            Duration::hours(*h),
+                                            "")
+                 })
+            .collect()
+    }
+
+    fn hours(day: &OvertimeDay) -> (i64, i64, i64) {
+        (day.regular.num_hours(), day.overtime.num_hours(), day.double.num_hours())
+    }
+
+    #[test]
+    fn weekly_test() {
+        let weeks = overtime(&week(&[9, 9, 9, 9, 9]), &OvertimeRules::new(), Weekday::Mon);
+        assert_eq!(weeks.len(), 1);
+        assert_eq!(hours(&weeks[0].days[3]), (9, 0, 0));
+        assert_eq!(hours(&weeks[0].days[4]), (4, 5, 0));
+        assert_eq!(hours(&weeks[0].total()), (40, 5, 0));
+
+        // weeks are counted separately
+        let weeks = overtime(&week(&[9, 9, 9, 9, 9, 0, 0, 9]),
+                             &OvertimeRules::new(),
+                             Weekday::Mon);
+        assert_eq!(weeks.len(), 2);
+        assert_eq!(hours(&weeks[1].total()), (9, 0, 0));
+    }
+
+    #[test]
+    fn daily_test() {
+        let rules = OvertimeRules {
+            daily: Some(Duration::hours(8)),
+            daily_double: Some(Duration::hours(12)),
+            weekly: Some(Duration::hours(40)),
+            seventh_day: false,
+        };
+        let weeks = overtime(&week(&[13, 10, 8, 8, 8, 8]), &rules, Weekday::Mon);
+        let days = &weeks[0].days;
+        assert_eq!(hours(&days[0]), (8, 4, 1));
+        assert_eq!(hours(&days[1]), (8, 2, 0));
+        // daily overtime doesn't count toward the weekly limit
+        assert_eq!(hours(&days[4]), (8, 0, 0));
+        assert_eq!(hours(&days[5]), (0, 8, 0));
+        assert_eq!(hours(&weeks[0].total()), (40, 14, 1));
+    }
+
+    #[test]
+    fn seventh_day_test() {
+        let rules = OvertimeRules {
+            daily: Some(Duration::hours(8)),
+            daily_double: None,
+            weekly: None,
+            seventh_day: true,
+        };
+        let weeks = overtime(&week(&[4, 4, 4, 4, 4, 4, 10]), &rules, Weekday::Mon);
+        assert_eq!(hours(&weeks[0].days[6]), (0, 8, 2));
+
+        // six days isn't enough
+        let weeks = overtime(&week(&[4, 4, 4, 4, 4, 0, 10]), &rules, Weekday::Mon);
+        assert_eq!(hours(&weeks[0].days[5]), (8, 2, 0));
+    }
+}
@@ -0,0 +1,210 @@
+// Repairs for gaps in the timesheet.
+//
+// Pairing entries up makes do with an In that has no Out after it, or an Out
+// with no In before it, by making up the missing entry. A `Gap` is one of
+// those, with the times the real entry would have to fall between and a
+// suggestion at the usual start or end of the day.
+
+use chrono::prelude::*;
+use timeclock::{Direction, PairState, TimeClockError, TimeEntry, TimeEntryPair};
+use timeclock::{inserted_entry, timeentry_pairs};
+
+/// An entry missing the entry that should be paired with it
+#[derive(Clone,Debug,PartialEq)]
+pub struct Gap {
+    /// The entry without a partner
+    pub entry: TimeEntry,
+    /// Its index in the entries the gap was found in
+    pub index: usize,
+    /// The missing entry can't be before this
+    pub earliest: Option<DateTime<FixedOffset>>,
+    /// or after this
+    pub latest: Option<DateTime<FixedOffset>>,
+    /// A time for the missing entry, if the usual one fits
+    pub proposed: Option<DateTime<FixedOffset>>,
+}
+
+impl Gap {
+    fn new(entry: &TimeEntry,
+           index: usize,
+           earliest: Option<DateTime<FixedOffset>>,
+           latest: Option<DateTime<FixedOffset>>,
+           usual: Option<DateTime<FixedOffset>>)
+           -> Gap {
+        let mut gap = Gap {
+            entry: entry.clone(),
+            index: index,
+            earliest: earliest,
+            latest: latest,
+            proposed: None,
+        };
+        gap.proposed = usual.and_then(|t| if gap.fits(t) { Some(t) } else { None });
+        gap
+    }
+
+    /// The direction of the missing entry
+    pub fn missing(&self) -> Direction {
+        match self.entry.dir {
+            Direction::In => Direction::Out,
+            Direction::Out => Direction::In,
+        }
+    }
+
+    /// Whether the missing entry could go at `time`
+    pub fn fits(&self, time: DateTime<FixedOffset>) -> bool {
+        self.earliest.map_or(true, |t| time >= t) && self.latest.map_or(true, |t| time <= t)
+    }
+
+    /// The missing entry at `time`, with the project of the entry it pairs
+    /// with
+    pub fn fill(&self, time: DateTime<FixedOffset>) -> Result<TimeEntry, TimeClockError> {
+        if !self.fits(time) {
+            return Err(TimeClockError::MissingOutOfRange);
+        }
+        let mut entry = inserted_entry(self.missing(), time);
+        entry.project = self.entry.project.clone();
+        Ok(entry)
+    }
+}
+
+/// The gaps in time ordered `entries`. A missing Out is proposed at
+/// `day_end` and a missing In at `day_start` on the same day.
+pub fn gaps(entries: &[TimeEntry], day_start: NaiveTime, day_end: NaiveTime) -> Vec<Gap> {
+    let pairs: Vec<TimeEntryPair> = timeentry_pairs(entries.iter().cloned()).collect();
+    let mut gaps = Vec::new();
+    // time of the last real entry, and the index of the next one
+    let mut last = None;
+    let mut index = 0;
+    for (k, pair) in pairs.iter().enumerate() {
+        match pair.state() {
+            PairState::MissingOut => {
+                let start = pair.start();
+                let next = pairs.get(k + 1).map(|p| p.start().time);
+                let usual = start.time.date().and_time(day_end);
+                gaps.push(Gap::new(start, index, Some(start.time), next, usual));
+                last = Some(start.time);
+                index += 1;
+            }
+            PairState::MissingIn => {
+                let end = pair.end();
+                let usual = end.time.date().and_time(day_start);
+                gaps.push(Gap::new(end, index, last, Some(end.time), usual));
+                last = Some(end.time);
+                index += 1;
+            }
+            PairState::Complete => {
+                last = Some(pair.end().time);
+                index += 2;
+            }
+            PairState::Open => index += 1,
+        }
+    }
+    gaps
+}
+
+/// Insert the entries filling gaps found in `entries`. Each goes right next
+/// to the entry it pairs with, even if another entry has the same time.
+pub fn fill_gaps(entries: &mut Vec<TimeEntry>, mut fills: Vec<(Gap, TimeEntry)>) {
+    // from the end so the indexes stay put
+    fills.sort_by_key(|&(ref gap, _)| gap.index);
+    for (gap, entry) in fills.into_iter().rev() {
+        let at = match gap.missing() {
+            Direction::In => gap.index,
+            Direction::Out => gap.index + 1,
+        };
+        entries.insert(at, entry);
+    }
+}
+
+/// Indexes of the entries in time ordered `entries` that repeat an earlier
+/// entry
+pub fn duplicates(entries: &[TimeEntry]) -> Vec<usize> {
+    (0..entries.len())
+        .filter(|&i| {
+                    entries[..i]
+                        .iter()
+                        .rev()
+                        .take_while(|e| e.time == entries[i].time)
+                        .any(|e| *e == entries[i])
+                })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use timeclock::read_timesheet;
+
+    fn entries(s: &str) -> Vec<TimeEntry> {
+        read_timesheet(Cursor::new(s.as_bytes())).unwrap()
+    }
+
+    fn time(s: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    #[test]
+    fn gaps_test() {
+        let e = entries("Out,2017-01-02T12:00:00-06:00,\n\
+                         In,2017-01-03T09:00:00-06:00,,acme\n\
+                         In,2017-01-04T09:00:00-06:00,\n\
+                         Out,2017-01-04T17:00:00-06:00,\n\
+                         In,2017-01-05T18:00:00-06:00,\n\
+                         In,2017-01-05T19:00:00-06:00,\n\
+                         Out,2017-01-05T20:00:00-06:00,\n\
+                         Out,2017-01-06T12:00:00-06:00,\n");
+        let g = gaps(&e, NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(17, 0, 0));
+        assert_eq!(g.len(), 4);
+
+        // nothing before the first entry
+        assert_eq!(g[0].missing(), Direction::In);
+        assert_eq!(g[0].earliest, None);
+        assert_eq!(g[0].proposed, Some(time("2017-01-02T09:00:00-06:00")));
+
+        assert_eq!(g[1].entry, e[1]);
+        assert_eq!(g[1].index, 1);
+        assert_eq!(g[1].latest, Some(e[2].time));
+        assert_eq!(g[1].proposed, Some(time("2017-01-03T17:00:00-06:00")));
+        let out = g[1].fill(time("2017-01-03T16:00:00-06:00")).unwrap();
+        assert_eq!(out.dir, Direction::Out);
+        assert_eq!(out.project, "acme");
+        assert!(g[1].fill(time("2017-01-04T10:00:00-06:00")).is_err());
+
+        // clocked in after the end of the day
+        assert_eq!(g[2].entry, e[4]);
+        assert_eq!(g[2].proposed, None);
+
+        assert_eq!(g[3].entry, e[7]);
+        assert_eq!(g[3].earliest, Some(e[6].time));
+        assert_eq!(g[3].proposed, Some(time("2017-01-06T09:00:00-06:00")));
+
+        // an Out at the same time as the next In goes before it
+        let mut filled = e.clone();
+        let fills = g.iter()
+            .map(|gap| {
+                     let t = gap.proposed.or(gap.latest).unwrap();
+                     (gap.clone(), gap.fill(t).unwrap())
+                 })
+            .collect();
+        fill_gaps(&mut filled, fills);
+        assert_eq!(filled.len(), 12);
+        assert_eq!(filled[6], e[4]);
+        assert_eq!(filled[7].dir, Direction::Out);
+        assert_eq!(filled[7].time, e[5].time);
+        assert_eq!(filled[8], e[5]);
+        assert_eq!(gaps(&filled, NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(17, 0, 0)),
+                   vec![]);
+    }
+
+    #[test]
+    fn duplicates_test() {
+        let e = entries("In,2017-01-02T09:00:00-06:00,\n\
+                         In,2017-01-02T09:00:00-06:00,other memo\n\
+                         In,2017-01-02T09:00:00-06:00,\n\
+                         Out,2017-01-02T17:00:00-06:00,\n\
+                         Out,2017-01-02T17:00:00-06:00,\n");
+        assert_eq!(duplicates(&e), vec![2, 4]);
+    }
+}
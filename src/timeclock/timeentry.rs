@@ -7,6 +7,13 @@ pub struct TimeEntry {
     pub dir: Direction,
     pub time: DateTime<FixedOffset>,
     pub memo: String,
+    #[serde(default)]
+    pub project: String,
+    #[serde(default, with = "tags")]
+    pub tags: Vec<String>,
+    /// The time as punched, if `time` has been rounded. Never written out.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub raw_time: Option<DateTime<FixedOffset>>,
 }
 
 impl TimeEntry {
@@ -18,26 +25,90 @@ impl TimeEntry {
             dir: dir,
             time: time,
             memo: memo.to_owned(),
+            project: String::new(),
+            tags: Vec::new(),
+            raw_time: None,
         }
     }
+
+    /// The project and tags formatted for display, eg. "[acme] #billable"
+    pub fn labels(&self) -> String {
+        let mut labels: Vec<String> = Vec::new();
+        if !self.project.is_empty() {
+            labels.push(format!("[{}]", self.project));
+        }
+        for tag in &self.tags {
+            labels.push(format!("#{}", tag));
+        }
+        labels.join(" ")
+    }
 }
 
 impl fmt::Display for TimeEntry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let time = self.time.format("%F %I:%M %P");
-        f.write_fmt(format_args!("{:3} {} {}", self.dir, time, self.memo))
+        try!(f.write_fmt(format_args!("{:3} {} {}", self.dir, time, self.memo)));
+        let labels = self.labels();
+        if !labels.is_empty() {
+            try!(f.write_fmt(format_args!(" {}", labels)));
+        }
+        Ok(())
+    }
+}
+
+/// Tags are stored in a single csv field, separated by spaces
+mod tags {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(tags: &Vec<String>, s: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        s.serialize_str(&tags.join(" "))
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<Vec<String>, D::Error>
+        where D: Deserializer<'de>
+    {
+        let s = try!(String::deserialize(d));
+        Ok(s.split_whitespace().map(String::from).collect())
     }
 }
 
+/// How a `TimeEntryPair` came about
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum PairState {
+    /// An In followed by an Out
+    Complete,
+    /// Still clocked in, the end is the current time
+    Open,
+    /// An Out without an In, the start is a copy of the end
+    MissingIn,
+    /// An In without an Out, the end is a copy of the start
+    MissingOut,
+}
+
 #[derive(Debug,PartialEq)]
 pub struct TimeEntryPair {
     start: TimeEntry,
     end: TimeEntry,
+    state: PairState,
 }
 
 impl TimeEntryPair {
     pub fn new(s: TimeEntry, e: TimeEntry) -> Self {
-        TimeEntryPair { start: s, end: e }
+        TimeEntryPair::with_state(s, e, PairState::Complete)
+    }
+
+    pub fn with_state(s: TimeEntry, e: TimeEntry, state: PairState) -> Self {
+        TimeEntryPair {
+            start: s,
+            end: e,
+            state: state,
+        }
+    }
+
+    pub fn state(&self) -> PairState {
+        self.state
     }
 
     pub fn start(&self) -> &TimeEntry {
@@ -67,7 +138,19 @@ mod tests {
         assert_eq!(display, "In  2017-01-05 02:04 pm Test");
         let debug = format!("{:?}", te);
         assert_eq!(debug,
-                   "TimeEntry { dir: In, time: 2017-01-05T14:04:16-06:00, memo: \"Test\" }");
+                   "TimeEntry { dir: In, time: 2017-01-05T14:04:16-06:00, \
+                   memo: \"Test\", project: \"\", tags: [], raw_time: None }");
+    }
+
+    #[test]
+    fn timeentry_labels_test() {
+        let mut te = TimeEntry::new(Direction::In, time_helper(), "Test");
+        assert_eq!(te.labels(), "");
+        te.project = String::from("acme");
+        te.tags = vec![String::from("billable"), String::from("urgent")];
+        assert_eq!(te.labels(), "[acme] #billable #urgent");
+        assert_eq!(format!("{}", te),
+                   "In  2017-01-05 02:04 pm Test [acme] #billable #urgent");
     }
 
     #[test]
@@ -78,5 +161,6 @@ mod tests {
         let tep = TimeEntryPair::new(te_a.clone(), te_b.clone());
         assert_eq!(tep.start(), &te_a);
         assert_eq!(tep.end(), &te_b);
+        assert_eq!(tep.state(), PairState::Complete);
     }
 }
@@ -1,114 +1,551 @@
 
+use chrono::prelude::*;
+use chrono::Duration;
+use std::fmt;
+use std::str::FromStr;
+
 /// Rounding modes for round()
 #[derive(Copy,Clone,Debug,PartialEq)]
 pub enum Rounding {
-    Up(f32),
-    Down(f32),
-    Half(f32),
+    Up(Duration),
+    Down(Duration),
+    Half(Duration),
+    /// Round up when more than the threshold into the increment, otherwise
+    /// down
+    Threshold(Duration, Duration),
+    /// Round like Threshold, but charge at least the minimum for any time
+    /// worked
+    Minimum(Duration, Duration, Duration),
     None,
 }
 
 impl Rounding {
-    fn seconds(&self) -> f32 {
+    /// The increment rounded to, zero for None
+    pub fn increment(&self) -> Duration {
         match *self {
             Rounding::Up(r) |
             Rounding::Down(r) |
-            Rounding::Half(r) => r,
-            Rounding::None => 0.0,
+            Rounding::Half(r) |
+            Rounding::Threshold(r, _) |
+            Rounding::Minimum(r, _, _) => r,
+            Rounding::None => Duration::zero(),
+        }
+    }
+
+    /// How far into the increment a duration can be and still round down
+    pub fn threshold(&self) -> Duration {
+        match *self {
+            Rounding::Up(_) |
+            Rounding::None => Duration::zero(),
+            Rounding::Down(r) => r,
+            Rounding::Half(r) => r / 2,
+            Rounding::Threshold(_, t) |
+            Rounding::Minimum(_, t, _) => t,
+        }
+    }
+
+    /// The same increment, rounding up only past `threshold`
+    pub fn with_threshold(self, threshold: Duration) -> Rounding {
+        match self {
+            Rounding::Minimum(r, _, m) => Rounding::Minimum(r, threshold, m),
+            r => Rounding::Threshold(r.increment(), threshold),
+        }
+    }
+
+    /// The same rounding, charging at least `minimum`
+    pub fn with_minimum(self, minimum: Duration) -> Rounding {
+        Rounding::Minimum(self.increment(), self.threshold(), minimum)
+    }
+}
+
+/// Formats in the same syntax `parsers::parse_rounding` accepts
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Rounding::Up(r) => write!(f, "+{}s", seconds(r)),
+            Rounding::Down(r) => write!(f, "-{}s", seconds(r)),
+            Rounding::Half(r) => write!(f, "={}s", seconds(r)),
+            Rounding::Threshold(r, t) => {
+                write!(f, "={}s,threshold={}s", seconds(r), seconds(t))
+            }
+            Rounding::Minimum(r, t, m) => {
+                write!(f,
+                       "={}s,threshold={}s,min={}s",
+                       seconds(r),
+                       seconds(t),
+                       seconds(m))
+            }
+            Rounding::None => write!(f, "=0s"),
         }
     }
 }
 
-/// Round times with a Rounding mode
-pub fn round(seconds: f64, rounding: Rounding) -> f64 {
-    // Pre-round. Not ideal but this prevents times like 3.0001234 from displaying
-    // surprisingly differently than the rounded versions. Eg displaying as 3.00
-    // when unrounded, but 3.25 when rounded up.
-    let seconds = {
-        if rounding.seconds() >= 36.0 {
-            (seconds / 36.0).round() * 36.0
+/// Rounding of punch times to an increment of the clock, eg. to the nearest
+/// quarter hour with the 7 minute rule. Times more than `threshold` past an
+/// increment round up, anything else rounds down.
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct PunchRounding {
+    pub increment: Duration,
+    pub threshold: Duration,
+}
+
+impl PunchRounding {
+    /// The threshold follows the direction of `rounding` unless given
+    pub fn new(rounding: Rounding, threshold: Option<Duration>) -> PunchRounding {
+        PunchRounding {
+            increment: rounding.increment(),
+            threshold: threshold.unwrap_or(rounding.threshold()),
+        }
+    }
+
+    /// Round `time` to an increment counted from midnight
+    pub fn round(&self, time: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        let since = time.signed_duration_since(time.date().and_hms(0, 0, 0));
+        let down = round(since, Rounding::Down(self.increment));
+        let rounded = if since - down > self.threshold {
+            down + self.increment
         } else {
-            seconds
+            down
+        };
+        time + (rounded - since)
+    }
+}
+
+/// Formats in the same syntax `parsers::parse_punch_rounding` accepts
+impl fmt::Display for PunchRounding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "={}s,threshold={}s",
+               seconds(self.increment),
+               seconds(self.threshold))
+    }
+}
+
+/// What a Rounding is applied to
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum RoundingScope {
+    /// Each In and Out pair
+    Pair,
+    /// Each day's total
+    Day,
+    /// Each week's total
+    Week,
+    /// The total for the whole report
+    Period,
+}
+
+impl FromStr for RoundingScope {
+    type Err = ();
+    fn from_str(s: &str) -> Result<RoundingScope, ()> {
+        match s.to_lowercase().as_ref() {
+            "pair" => Ok(RoundingScope::Pair),
+            "day" => Ok(RoundingScope::Day),
+            "week" => Ok(RoundingScope::Week),
+            "period" => Ok(RoundingScope::Period),
+            _ => Err(()),
         }
+    }
+}
+
+impl fmt::Display for RoundingScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            RoundingScope::Pair => "pair",
+            RoundingScope::Day => "day",
+            RoundingScope::Week => "week",
+            RoundingScope::Period => "period",
+        };
+        fmt::Display::fmt(s, f)
+    }
+}
+
+/// How often pay periods come around
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum PayPeriod {
+    /// Weeks starting on the configured week start
+    Weekly,
+    /// Two weeks at a time, counted from the first day of any pay period
+    BiWeekly(NaiveDate),
+    /// The 1st to the 15th, and the 16th to the end of the month
+    SemiMonthly,
+}
+
+impl FromStr for PayPeriod {
+    type Err = ();
+    fn from_str(s: &str) -> Result<PayPeriod, ()> {
+        let mut words = s.split_whitespace();
+        let period = words.next().unwrap_or("").to_lowercase();
+        let anchor = words.next();
+        if words.next().is_some() {
+            return Err(());
+        }
+        match (period.as_ref(), anchor) {
+            ("weekly", None) => Ok(PayPeriod::Weekly),
+            ("semimonthly", None) |
+            ("semi-monthly", None) => Ok(PayPeriod::SemiMonthly),
+            ("biweekly", Some(d)) |
+            ("bi-weekly", Some(d)) => {
+                NaiveDate::parse_from_str(d, "%F").map(PayPeriod::BiWeekly).map_err(|_| ())
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for PayPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PayPeriod::Weekly => write!(f, "weekly"),
+            PayPeriod::BiWeekly(d) => write!(f, "biweekly {}", d.format("%F")),
+            PayPeriod::SemiMonthly => write!(f, "semimonthly"),
+        }
+    }
+}
+
+/// What report rows are grouped into
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Grouping {
+    Week,
+    Month,
+    Year,
+    PayPeriod,
+}
+
+impl FromStr for Grouping {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Grouping, ()> {
+        match s.to_lowercase().as_ref() {
+            "week" => Ok(Grouping::Week),
+            "month" => Ok(Grouping::Month),
+            "year" => Ok(Grouping::Year),
+            "pay-period" => Ok(Grouping::PayPeriod),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Grouping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Grouping::Week => "week",
+            Grouping::Month => "month",
+            Grouping::Year => "year",
+            Grouping::PayPeriod => "pay-period",
+        };
+        fmt::Display::fmt(s, f)
+    }
+}
+
+/// The first and last days of the week, month, year or pay period
+/// containing `date`
+pub fn period(date: Date<FixedOffset>,
+              grouping: Grouping,
+              start: Weekday,
+              pay_period: PayPeriod)
+              -> (Date<FixedOffset>, Date<FixedOffset>) {
+    let ofst = *date.offset();
+    let day = date.naive_local();
+    let ymd = |y, m, d| Date::from_utc(NaiveDate::from_ymd(y, m, d), ofst);
+    let month_end = || if day.month() == 12 {
+        ymd(day.year(), 12, 31)
+    } else {
+        ymd(day.year(), day.month() + 1, 1) - Duration::days(1)
     };
+    let week = |start: Date<FixedOffset>| (start, start + Duration::days(6));
+
+    match (grouping, pay_period) {
+        (Grouping::Week, _) |
+        (Grouping::PayPeriod, PayPeriod::Weekly) => week(week_start(date, start)),
+        (Grouping::Month, _) => (ymd(day.year(), day.month(), 1), month_end()),
+        (Grouping::Year, _) => (ymd(day.year(), 1, 1), ymd(day.year(), 12, 31)),
+        (Grouping::PayPeriod, PayPeriod::BiWeekly(anchor)) => {
+            let days = day.signed_duration_since(anchor).num_days();
+            let start = Date::from_utc(anchor, ofst) +
+                        Duration::days(days - ((days % 14) + 14) % 14);
+            (start, start + Duration::days(13))
+        }
+        (Grouping::PayPeriod, PayPeriod::SemiMonthly) => {
+            if day.day() <= 15 {
+                (ymd(day.year(), day.month(), 1), ymd(day.year(), day.month(), 15))
+            } else {
+                (ymd(day.year(), day.month(), 16), month_end())
+            }
+        }
+    }
+}
+
+/// The first day of the week containing `date`, for weeks starting on
+/// `start`
+pub fn week_start(date: Date<FixedOffset>, start: Weekday) -> Date<FixedOffset> {
+    let weekday = date.weekday().num_days_from_monday() as i64;
+    let weekstart = start.num_days_from_monday() as i64;
+    let days_back = (7 - weekstart + weekday) % 7;
+    date - Duration::days(days_back)
+}
 
+/// Round a duration with a Rounding mode. The arithmetic is done on whole
+/// nanoseconds so the result is exact.
+pub fn round(duration: Duration, rounding: Rounding) -> Duration {
+    let res = round_increment(duration, rounding);
+    match rounding {
+        Rounding::Minimum(_, _, m) if duration > Duration::zero() && res < m => m,
+        _ => res,
+    }
+}
+
+/// Round a duration to the increment, without the minimum
+fn round_increment(duration: Duration, rounding: Rounding) -> Duration {
+    let increment = rounding.increment().num_nanoseconds();
+    let (r, n) = match (increment, duration.num_nanoseconds()) {
+        (Some(r), Some(n)) if r > 0 => (r, n),
+        _ => return duration,
+    };
+
+    // remainder towards negative infinity, so it's always 0 <= rem < r
+    let rem = ((n % r) + r) % r;
+    let down = n - rem;
     let res = match rounding {
-        Rounding::Up(r) => (seconds / r as f64).ceil() * r as f64,
-        Rounding::Down(r) => (seconds / r as f64).floor() * r as f64,
-        Rounding::Half(r) => (seconds / r as f64).round() * r as f64,
-        Rounding::None => seconds,
+        Rounding::Up(_) if rem > 0 => down + r,
+        Rounding::Half(_) if rem >= r - rem => down + r,
+        Rounding::Threshold(_, t) |
+        Rounding::Minimum(_, t, _) if Duration::nanoseconds(rem) > t => down + r,
+        _ => down,
     };
-    // don't return NaN
-    if res.is_nan() { seconds } else { res }
+    Duration::nanoseconds(res)
+}
+
+/// A duration in seconds, for display
+pub fn seconds(duration: Duration) -> f64 {
+    match duration.num_nanoseconds() {
+        Some(n) => n as f64 / 1e9,
+        None => duration.num_seconds() as f64,
+    }
+}
+
+/// A duration in hours, for display
+pub fn hours(duration: Duration) -> f64 {
+    seconds(duration) / 3600.0
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use parsers;
+
+    fn time_actual() -> Duration {
+        // ~10:36am
+        Duration::nanoseconds(38_160_123_450_000)
+    }
+
+    fn time_up() -> Duration {
+        // 10:45am
+        Duration::seconds(38700)
+    }
 
-    const TIME_ACTUAL: f64 = 38160.12345; // ~10:36am in seconds
-    const TIME_UP: f64 = 38700.0; // 10:45am in seconds
-    const TIME_DOWN: f64 = 37800.0; // 10:30am in seconds
+    fn time_down() -> Duration {
+        // 10:30am
+        Duration::seconds(37800)
+    }
+
+    fn quarter() -> Rounding {
+        Rounding::Up(Duration::seconds(900))
+    }
 
     #[test]
     fn round_up_test() {
         // round up
-        let res = round(TIME_ACTUAL, Rounding::Up(900.0));
-        assert_eq!(res, TIME_UP);
-        let res = round(TIME_UP, Rounding::Up(900.0));
-        assert_eq!(res, TIME_UP);
-        let res = round(TIME_UP - 1.0, Rounding::Up(900.0));
-        assert_eq!(res, TIME_UP);
-        assert_eq!(TIME_UP, TIME_UP);
+        assert_eq!(round(time_actual(), quarter()), time_up());
+        assert_eq!(round(time_up(), quarter()), time_up());
+        assert_eq!(round(time_up() - Duration::seconds(1), quarter()),
+                   time_up());
 
-        // test pre-rounding
-        let res = round(37800.000045, Rounding::Up(900.0));
-        assert_eq!(res, 37800.0);
+        // no pre-rounding, a nanosecond over is the next increment
+        let res = round(time_down() + Duration::nanoseconds(1), quarter());
+        assert_eq!(res, time_up());
     }
 
     #[test]
     fn round_down_test() {
         // round down
-        let res = round(TIME_ACTUAL, Rounding::Down(900.0));
-        assert_eq!(res, TIME_DOWN);
-        let res = round(TIME_DOWN, Rounding::Down(900.0));
-        assert_eq!(res, TIME_DOWN);
-        let res = round(TIME_DOWN + 1.0, Rounding::Down(900.0));
-        assert_eq!(res, TIME_DOWN);
-
-        // test pre-rounding
-        let res = round(37790.0, Rounding::Down(900.0));
-        assert_eq!(res, 37800.0);
+        let down = Rounding::Down(Duration::seconds(900));
+        assert_eq!(round(time_actual(), down), time_down());
+        assert_eq!(round(time_down(), down), time_down());
+        assert_eq!(round(time_down() + Duration::seconds(1), down),
+                   time_down());
+        assert_eq!(round(time_up() - Duration::nanoseconds(1), down),
+                   time_down());
     }
 
     #[test]
     fn round_half_test() {
         // round half
-        let res = round(TIME_ACTUAL, Rounding::Half(900.0));
-        assert_eq!(res, TIME_DOWN);
-        let res = round(TIME_DOWN, Rounding::Half(900.0));
-        assert_eq!(res, TIME_DOWN);
+        let half = Rounding::Half(Duration::seconds(900));
+        assert_eq!(round(time_actual(), half), time_down());
+        assert_eq!(round(time_down(), half), time_down());
+        // exactly half way rounds up
+        assert_eq!(round(time_down() + Duration::seconds(450), half),
+                   time_up());
+        assert_eq!(round(time_down() + Duration::seconds(449), half),
+                   time_down());
+    }
+
+    #[test]
+    fn round_negative_test() {
+        let minute = Duration::minutes(1);
+        let d = Duration::seconds(-90);
+        assert_eq!(round(d, Rounding::Up(minute)), -minute);
+        assert_eq!(round(d, Rounding::Down(minute)), minute * -2);
+        assert_eq!(round(d, Rounding::Half(minute)), -minute);
     }
 
     #[test]
     fn round_quarter_test() {
         // round to 1/4 sec
-        let res = round(TIME_ACTUAL, Rounding::Up(0.25));
-        assert_eq!(res, 38160.25);
+        let res = round(time_actual(), Rounding::Up(Duration::milliseconds(250)));
+        assert_eq!(res, Duration::milliseconds(38_160_250));
     }
 
     #[test]
     fn round_zero_test() {
         // round with zero
-        let res = round(TIME_ACTUAL, Rounding::Up(0.0));
-        assert_eq!(res, TIME_ACTUAL);
+        let res = round(time_actual(), Rounding::Up(Duration::zero()));
+        assert_eq!(res, time_actual());
+    }
+
+    #[test]
+    fn round_exact_test() {
+        // a year of 7h 59m 59.9s days doesn't drift
+        let day = Duration::milliseconds(((7 * 60 + 59) * 60 + 59) * 1000 + 900);
+        let total = (0..365).fold(Duration::zero(), |acc, _| acc + day);
+        assert_eq!(total, Duration::milliseconds(10_511_963_500));
+        assert_eq!(round(total, Rounding::Half(Duration::minutes(1))),
+                   Duration::minutes(175_199));
+        assert_eq!(hours(Duration::minutes(90)), 1.5);
+    }
+
+    #[test]
+    fn punch_rounding_test() {
+        let time = |t: &str| {
+            DateTime::parse_from_rfc3339(&format!("2017-01-02T{}-06:00", t))
+                .unwrap()
+        };
+        let quarter = Duration::minutes(15);
+
+        // the 7 minute rule
+        let seven = PunchRounding::new(Rounding::Half(quarter), Some(Duration::minutes(7)));
+        assert_eq!(seven.round(time("08:07:00")), time("08:00:00"));
+        assert_eq!(seven.round(time("08:08:00")), time("08:15:00"));
+        assert_eq!(seven.round(time("08:52:00")), time("08:45:00"));
+        assert_eq!(seven.round(time("08:53:00")), time("09:00:00"));
+        assert_eq!(seven.round(time("08:45:00")), time("08:45:00"));
+
+        let up = PunchRounding::new(Rounding::Up(quarter), None);
+        assert_eq!(up.round(time("08:00:01")), time("08:15:00"));
+        assert_eq!(up.round(time("08:00:00")), time("08:00:00"));
+
+        let down = PunchRounding::new(Rounding::Down(quarter), None);
+        assert_eq!(down.round(time("16:59:59")), time("16:45:00"));
+
+        // rounding up past midnight moves to the next day
+        assert_eq!(up.round(time("23:50:00")).to_rfc3339(),
+                   "2017-01-03T00:00:00-06:00");
+
+        assert_eq!(seven.to_string(), "=900s,threshold=420s");
+    }
+
+    #[test]
+    fn week_start_test() {
+        // 2017-04-05 is a Wednesday
+        let date = FixedOffset::west(6 * 3600).ymd(2017, 4, 5);
+        assert_eq!(week_start(date, Weekday::Mon), date - Duration::days(2));
+        assert_eq!(week_start(date, Weekday::Wed), date);
+        assert_eq!(week_start(date, Weekday::Thu), date - Duration::days(6));
+        assert_eq!(week_start(date, Weekday::Sat), date - Duration::days(4));
+    }
+
+    #[test]
+    fn round_threshold_test() {
+        let quarter = Duration::minutes(15);
+        let rounding = Rounding::Threshold(quarter, Duration::minutes(5));
+        assert_eq!(round(Duration::minutes(65), rounding), Duration::minutes(60));
+        assert_eq!(round(Duration::minutes(66), rounding), Duration::minutes(75));
+        assert_eq!(round(Duration::minutes(74), rounding), Duration::minutes(75));
+        assert_eq!(Rounding::Down(quarter).with_threshold(Duration::minutes(5)),
+                   rounding);
+        assert_eq!(rounding.to_string(), "=900s,threshold=300s");
+    }
+
+    #[test]
+    fn round_minimum_test() {
+        let hour = Duration::hours(1);
+        let rounding = Rounding::Up(Duration::minutes(15)).with_minimum(hour);
+        assert_eq!(rounding,
+                   Rounding::Minimum(Duration::minutes(15), Duration::zero(), hour));
+        assert_eq!(round(Duration::minutes(5), rounding), hour);
+        assert_eq!(round(Duration::minutes(61), rounding), Duration::minutes(75));
+        // no time worked, nothing charged
+        assert_eq!(round(Duration::zero(), rounding), Duration::zero());
+        assert_eq!(rounding.to_string(), "=900s,threshold=0s,min=3600s");
+
+        // a minimum without an increment
+        let rounding = parsers::parse_rounding("=0m,min=1h").unwrap();
+        assert_eq!(round(Duration::minutes(5), rounding), hour);
+        assert_eq!(round(Duration::minutes(61), rounding), Duration::minutes(61));
+        assert_eq!(round(Duration::zero(), rounding), Duration::zero());
+    }
+
+    #[test]
+    fn period_test() {
+        let date = |d: &str| {
+            let d = NaiveDate::parse_from_str(d, "%F").unwrap();
+            FixedOffset::west(6 * 3600).from_local_date(&d).unwrap()
+        };
+        let bounds = |d, grouping, pay_period| {
+            let (start, end) = period(date(d), grouping, Weekday::Mon, pay_period);
+            (start.format("%F").to_string(), end.format("%F").to_string())
+        };
+        let pair = |a: &str, b: &str| (a.to_owned(), b.to_owned());
+        let weekly = PayPeriod::Weekly;
+
+        // 2017-02-15 is a Wednesday
+        assert_eq!(bounds("2017-02-15", Grouping::Week, weekly),
+                   pair("2017-02-13", "2017-02-19"));
+        assert_eq!(bounds("2017-02-15", Grouping::Month, weekly),
+                   pair("2017-02-01", "2017-02-28"));
+        assert_eq!(bounds("2017-12-15", Grouping::Month, weekly),
+                   pair("2017-12-01", "2017-12-31"));
+        assert_eq!(bounds("2017-02-15", Grouping::Year, weekly),
+                   pair("2017-01-01", "2017-12-31"));
+        assert_eq!(bounds("2017-02-15", Grouping::PayPeriod, weekly),
+                   pair("2017-02-13", "2017-02-19"));
+
+        let semi = PayPeriod::SemiMonthly;
+        assert_eq!(bounds("2017-02-15", Grouping::PayPeriod, semi),
+                   pair("2017-02-01", "2017-02-15"));
+        assert_eq!(bounds("2017-02-16", Grouping::PayPeriod, semi),
+                   pair("2017-02-16", "2017-02-28"));
+
+        let biweekly: PayPeriod = "biweekly 2017-01-06".parse().unwrap();
+        assert_eq!(biweekly, PayPeriod::BiWeekly(NaiveDate::from_ymd(2017, 1, 6)));
+        assert_eq!(bounds("2017-02-15", Grouping::PayPeriod, biweekly),
+                   pair("2017-02-03", "2017-02-16"));
+        assert_eq!(bounds("2017-02-17", Grouping::PayPeriod, biweekly),
+                   pair("2017-02-17", "2017-03-02"));
+        // before the anchor
+        assert_eq!(bounds("2017-01-05", Grouping::PayPeriod, biweekly),
+                   pair("2016-12-23", "2017-01-05"));
+
+        assert_eq!(biweekly.to_string().parse(), Ok(biweekly));
+        assert_eq!("semi-monthly".parse(), Ok(semi));
+        assert!("biweekly".parse::<PayPeriod>().is_err());
+        assert!("weekly 2017-01-06".parse::<PayPeriod>().is_err());
     }
 
     #[test]
     fn round_none_test() {
         // round None
-        let res = round(TIME_ACTUAL, Rounding::None);
-        assert_eq!(res, TIME_ACTUAL);
+        let res = round(time_actual(), Rounding::None);
+        assert_eq!(res, time_actual());
 
     }
 }